@@ -0,0 +1,372 @@
+//! This module defines the authentication of the API: the hashing of passwords, the
+//! password-reset tokens, the signed JWTs issued at login, and the `AuthUser`
+//! extractor that validates them.
+
+use crate::error::ErrorType;
+use crate::models::user_model::{Role, User};
+use crate::state::AppState;
+use argon2::{
+    Argon2,
+    password_hash::{
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+        rand_core::{OsRng, RngCore},
+    },
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::LazyLock;
+use tracing::{error, info};
+
+/// How many seconds a token is valid for after login.
+pub const TOKEN_LIFETIME_SECONDS: i64 = 8 * 60 * 60;
+
+/// How many minutes a password-reset token is valid for after it is issued.
+pub const RESET_TOKEN_LIFETIME_MINUTES: i64 = 30;
+
+/// Hashes a password with Argon2 and a random salt, returning the PHC string.
+/// Argon2 takes tens of milliseconds on purpose, so the hashing runs on the
+/// blocking thread pool rather than holding up the other requests.
+///
+/// # Errors
+///
+/// Returns `ErrorType::ServerError` if the password cannot be hashed.
+pub async fn hash_password(password: &str) -> Result<String, ErrorType> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || hash_password_blocking(&password))
+        .await
+        .map_err(|err| {
+            error!(error = %err, "Error joining the password hashing");
+            ErrorType::ServerError("Server Error".to_string())
+        })?
+}
+
+/// Tells whether a password matches an Argon2 hash in PHC string format. The check
+/// runs on the blocking thread pool, like the hashing.
+pub async fn verify_password(password: &str, password_hash: &str) -> bool {
+    let (password, password_hash) = (password.to_string(), password_hash.to_string());
+    run_verification(move || verify_password_blocking(&password, &password_hash)).await
+}
+
+/// The hash a login checks the password against when no account has the email, so
+/// that the response takes as long as for a wrong password and does not reveal
+/// which emails have an account.
+static DUMMY_PASSWORD_HASH: LazyLock<String> =
+    LazyLock::new(|| hash_password_blocking(&generate_reset_token()).unwrap_or_default());
+
+/// Tells whether a password matches the hash of an account. Without an account, the
+/// password is still checked against a dummy hash before `false` is returned. The
+/// check runs on the blocking thread pool, like the hashing.
+pub async fn verify_login(password: &str, password_hash: Option<&str>) -> bool {
+    let password = password.to_string();
+    let password_hash = password_hash.map(str::to_string);
+    run_verification(move || match password_hash {
+        Some(password_hash) => verify_password_blocking(&password, &password_hash),
+        None => {
+            verify_password_blocking(&password, &DUMMY_PASSWORD_HASH);
+            false
+        }
+    })
+    .await
+}
+
+/// Hashes a password on the current thread.
+fn hash_password_blocking(password: &str) -> Result<String, ErrorType> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| {
+            error!(error = %err, "Error hashing password");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+}
+
+/// Checks a password against a hash on the current thread.
+fn verify_password_blocking(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Runs a password check on the blocking thread pool. A check that cannot complete
+/// counts as a mismatch.
+async fn run_verification(check: impl FnOnce() -> bool + Send + 'static) -> bool {
+    tokio::task::spawn_blocking(check)
+        .await
+        .unwrap_or_else(|err| {
+            error!(error = %err, "Error joining the password check");
+            false
+        })
+}
+
+/// Generates a random password-reset token of 32 bytes, encoded as URL-safe base64.
+pub fn generate_reset_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Returns the hex-encoded SHA-256 hash of a password-reset token, which is what
+/// gets stored. A fast hash is enough since the token is random and long.
+pub fn hash_reset_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The claims carried by the JWTs issued at login.
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    /// The ID of the user
+    sub: String,
+    /// The role of the user
+    role: Role,
+    /// The ID of the Student the user acts as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    student_id: Option<String>,
+    /// The ID of the Teacher the user acts as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    teacher_id: Option<String>,
+    /// When the token was issued, in seconds since the epoch
+    iat: i64,
+    /// When the token expires, in seconds since the epoch
+    exp: i64,
+}
+
+/// The keys used to sign and verify the JWTs, derived from a shared secret.
+pub struct TokenKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl TokenKeys {
+    /// Creates the keys of the HS256 algorithm from a secret.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+
+    /// Issues a signed token for a user, valid for `TOKEN_LIFETIME_SECONDS`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerError` if the token cannot be signed.
+    pub fn issue(&self, user: &User) -> Result<String, ErrorType> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: user.id.unwrap_or_default().to_hex(),
+            role: user.role,
+            student_id: user.student_id.map(|id| id.to_hex()),
+            teacher_id: user.teacher_id.map(|id| id.to_hex()),
+            iat: now,
+            exp: now + TOKEN_LIFETIME_SECONDS,
+        };
+
+        jsonwebtoken::encode(&Header::default(), &claims, &self.encoding).map_err(|err| {
+            error!(error = %err, "Error signing token");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    /// Verifies the signature and expiry of a token and returns the user it was
+    /// issued for.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Unauthorized` if the token is not valid or has expired.
+    pub fn verify(&self, token: &str) -> Result<AuthUser, ErrorType> {
+        let invalid = || ErrorType::Unauthorized("The token is not valid".to_string());
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &self.decoding, &Validation::default())
+            .map_err(|_| invalid())?
+            .claims;
+        let parse = |id: Option<String>| {
+            id.map(|id| ObjectId::parse_str(id).map_err(|_| invalid()))
+                .transpose()
+        };
+
+        Ok(AuthUser {
+            user_id: ObjectId::parse_str(&claims.sub).map_err(|_| invalid())?,
+            role: claims.role,
+            student_id: parse(claims.student_id)?,
+            teacher_id: parse(claims.teacher_id)?,
+            issued_at: claims.iat,
+        })
+    }
+}
+
+/// The user calling a route, as identified by the bearer token of the request.
+///
+/// Adding an `AuthUser` argument to a handler rejects the requests without a valid
+/// token with `401 Unauthorized`; the handler then checks the role of the user. A
+/// token is only valid while its account exists and its password is unchanged, and
+/// the role and the student or teacher are read from the account, so a change to
+/// the account applies to the tokens already issued.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    /// The ID of the user
+    pub user_id: ObjectId,
+    /// The role of the user
+    pub role: Role,
+    /// The ID of the Student the user acts as
+    pub student_id: Option<ObjectId>,
+    /// The ID of the Teacher the user acts as
+    pub teacher_id: Option<ObjectId>,
+    /// When the token was issued, in seconds since the epoch
+    pub issued_at: i64,
+}
+
+impl AuthUser {
+    /// Checks that the user has one of the given roles.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Forbidden` if the user has none of the roles.
+    pub fn require(&self, roles: &[Role]) -> Result<(), ErrorType> {
+        if roles.contains(&self.role) {
+            Ok(())
+        } else {
+            Err(ErrorType::Forbidden(
+                "You are not allowed to do this".to_string(),
+            ))
+        }
+    }
+
+    /// Checks that the user is the given student, or has one of the given roles.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Forbidden` if the user is another student and has none
+    /// of the roles.
+    pub fn require_student_or(
+        &self,
+        student_id: ObjectId,
+        roles: &[Role],
+    ) -> Result<(), ErrorType> {
+        if self.role == Role::Student && self.student_id == Some(student_id) {
+            return Ok(());
+        }
+
+        self.require(roles)
+    }
+
+    /// Returns the classes whose attendance the user may mark and read: `None` for
+    /// admins, who may access every class, and the classes assigned to the teacher
+    /// otherwise. Other roles are assigned no class.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerError` if the teacher cannot be fetched.
+    pub async fn assigned_classes(
+        &self,
+        state: &AppState,
+    ) -> Result<Option<Vec<ObjectId>>, ErrorType> {
+        if self.role == Role::Admin {
+            return Ok(None);
+        }
+
+        let teacher = match self.teacher_id.filter(|_| self.role == Role::Teacher) {
+            Some(teacher_id) => state.teachers.find_by_id(teacher_id).await?,
+            None => None,
+        };
+
+        Ok(Some(
+            teacher.map(|teacher| teacher.classes).unwrap_or_default(),
+        ))
+    }
+
+    /// Checks that the user may act on a class, such as managing its sessions or
+    /// marking and reading its attendance: admins may access every class, and
+    /// teachers the classes assigned to them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Forbidden` if the class is not assigned to the user.
+    pub async fn require_class(
+        &self,
+        state: &AppState,
+        class_id: ObjectId,
+    ) -> Result<(), ErrorType> {
+        match self.assigned_classes(state).await? {
+            Some(classes) if !classes.contains(&class_id) => Err(ErrorType::Forbidden(
+                "You are not assigned to the class".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = ErrorType;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(state) = parts.extensions.get::<AppState>() else {
+            error!("Error authenticating: the application state is missing");
+            return Err(ErrorType::ServerError("Server Error".to_string()));
+        };
+
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| ErrorType::Unauthorized("A bearer token is required".to_string()))?;
+        let user = state.tokens.verify(token)?;
+
+        let account = state
+            .users
+            .find_by_id(user.user_id)
+            .await?
+            .filter(|account| account.accepts_token_issued_at(user.issued_at))
+            .ok_or_else(|| ErrorType::Unauthorized("The token is no longer valid".to_string()))?;
+
+        Ok(AuthUser {
+            role: account.role,
+            student_id: account.student_id,
+            teacher_id: account.teacher_id,
+            ..user
+        })
+    }
+}
+
+/// Creates the admin account with the given email and password, unless a user with
+/// that email already exists. This gives a fresh database a first account to log
+/// in with.
+///
+/// # Errors
+///
+/// Returns `ErrorType::ServerError` if the account cannot be stored.
+pub async fn ensure_admin(state: &AppState, email: &str, password: &str) -> Result<(), ErrorType> {
+    if state.users.find_by_email(email).await?.is_some() {
+        return Ok(());
+    }
+
+    state
+        .users
+        .insert(User {
+            id: None,
+            email: email.to_string(),
+            password_hash: hash_password(password).await?,
+            role: Role::Admin,
+            student_id: None,
+            teacher_id: None,
+            password_changed_at: None,
+        })
+        .await?;
+
+    info!(email, "Created the admin account");
+    Ok(())
+}
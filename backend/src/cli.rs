@@ -0,0 +1,48 @@
+//! This module parses the command-line arguments of the server.
+
+use crate::error::ErrorType;
+
+/// How to run the binary, usable in the errors about the arguments.
+const USAGE: &str = "usage: backend [--migrate [--dry-run]]";
+
+/// What the binary is asked to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Serve the API, the default
+    Serve,
+    /// Apply the pending migrations and exit, or only list them on a dry run
+    Migrate { dry_run: bool },
+}
+
+impl Command {
+    /// Parses the arguments given to the binary, without the name of the binary.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerStartingError` with the usage if an argument is not
+    /// known, or if `--dry-run` is given without `--migrate`.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, ErrorType> {
+        let (mut migrate, mut dry_run) = (false, false);
+        for arg in args {
+            match arg.as_str() {
+                "--migrate" => migrate = true,
+                "--dry-run" => dry_run = true,
+                _ => {
+                    return Err(ErrorType::ServerStartingError(format!(
+                        "Unknown argument `{}`; {}",
+                        arg, USAGE
+                    )));
+                }
+            }
+        }
+
+        match (migrate, dry_run) {
+            (true, _) => Ok(Command::Migrate { dry_run }),
+            (false, true) => Err(ErrorType::ServerStartingError(format!(
+                "`--dry-run` needs `--migrate`; {}",
+                USAGE
+            ))),
+            (false, false) => Ok(Command::Serve),
+        }
+    }
+}
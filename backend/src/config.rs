@@ -0,0 +1,491 @@
+//! This module defines the configuration of the application. It is read from an
+//! optional TOML file, then overridden by environment variables, and validated before
+//! the server starts, so that a bad setting stops the server with a clear message
+//! instead of failing on the first request.
+
+use crate::error::ErrorType;
+use crate::telemetry::LogFormat;
+use serde::Deserialize;
+use std::{env, fmt::Display, path::Path, str::FromStr, time::Duration};
+
+/// The file read when `CONFIG_FILE` is not set. It is skipped if it does not exist.
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// The whole configuration of the application.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// Where the data is stored
+    pub storage: StorageBackend,
+    /// The HTTP server
+    pub server: ServerConfig,
+    /// The MongoDB connection and collections
+    pub database: DatabaseConfig,
+    /// The authentication tokens and the first admin account
+    pub auth: AuthConfig,
+    /// The transport of the emails
+    pub mail: MailConfig,
+    /// The logs
+    pub log: LogConfig,
+}
+
+/// Where the data is stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// In MongoDB
+    #[default]
+    Mongo,
+    /// In memory, lost when the server stops
+    Memory,
+}
+
+/// The settings of the HTTP server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The address the server listens on
+    pub host: String,
+    /// The port the server listens on
+    pub port: u16,
+    /// How long a dependency has to answer the readiness probe, in milliseconds
+    pub health_check_timeout_ms: u64,
+    /// How long the in-flight requests have to finish once a shutdown is requested,
+    /// in seconds
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            health_check_timeout_ms: 2000,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Returns how long a dependency has to answer the readiness probe.
+    pub fn health_check_timeout(&self) -> Duration {
+        Duration::from_millis(self.health_check_timeout_ms)
+    }
+
+    /// Returns how long the in-flight requests have to finish once a shutdown is
+    /// requested.
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+/// The settings of the MongoDB connection.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// The connection string, required when the storage is MongoDB
+    pub uri: Option<String>,
+    /// The name of the database
+    pub name: String,
+    /// The number of connections kept open even when idle
+    pub min_pool_size: u32,
+    /// The largest number of connections open at once
+    pub max_pool_size: u32,
+    /// How long opening a connection may take, in seconds
+    pub connect_timeout_secs: u64,
+    /// How long finding a server for an operation may take, in seconds
+    pub server_selection_timeout_secs: u64,
+    /// Whether the pending migrations are applied at startup, instead of stopping
+    /// the server
+    pub migrate_on_startup: bool,
+    /// The names of the collections
+    pub collections: CollectionNames,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            uri: None,
+            name: "attendance".to_string(),
+            min_pool_size: 0,
+            max_pool_size: 10,
+            connect_timeout_secs: 10,
+            server_selection_timeout_secs: 30,
+            migrate_on_startup: false,
+            collections: CollectionNames::default(),
+        }
+    }
+}
+
+impl DatabaseConfig {
+    /// Returns how long opening a connection may take.
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    /// Returns how long finding a server for an operation may take.
+    pub fn server_selection_timeout(&self) -> Duration {
+        Duration::from_secs(self.server_selection_timeout_secs)
+    }
+}
+
+/// The names of the MongoDB collections.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionNames {
+    /// The students
+    pub students: String,
+    /// The teachers
+    pub teachers: String,
+    /// The classes
+    pub classes: String,
+    /// The attendance records
+    pub records: String,
+    /// The attendance records of deleted students
+    pub archived_records: String,
+    /// The class enrollments
+    pub enrollments: String,
+    /// The class sessions
+    pub sessions: String,
+    /// The user accounts
+    pub users: String,
+    /// The password-reset tokens
+    pub password_resets: String,
+    /// The applied migrations
+    pub migrations: String,
+}
+
+impl Default for CollectionNames {
+    fn default() -> Self {
+        Self {
+            students: "students".to_string(),
+            teachers: "teachers".to_string(),
+            classes: "classes".to_string(),
+            records: "records".to_string(),
+            archived_records: "archived_records".to_string(),
+            enrollments: "enrollments".to_string(),
+            sessions: "sessions".to_string(),
+            users: "users".to_string(),
+            password_resets: "password_resets".to_string(),
+            migrations: "_migrations".to_string(),
+        }
+    }
+}
+
+impl CollectionNames {
+    /// Returns every collection name with the setting it comes from.
+    fn all(&self) -> [(&'static str, &str); 10] {
+        [
+            ("students", &self.students),
+            ("teachers", &self.teachers),
+            ("classes", &self.classes),
+            ("records", &self.records),
+            ("archived_records", &self.archived_records),
+            ("enrollments", &self.enrollments),
+            ("sessions", &self.sessions),
+            ("users", &self.users),
+            ("password_resets", &self.password_resets),
+            ("migrations", &self.migrations),
+        ]
+    }
+}
+
+/// The settings of the authentication.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// The secret signing the authentication tokens
+    pub jwt_secret: String,
+    /// The email of the admin account created at startup if it is missing
+    pub admin_email: Option<String>,
+    /// The password of the admin account created at startup if it is missing
+    pub admin_password: Option<String>,
+}
+
+/// The settings of the emails.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+    /// Where the emails go
+    pub transport: MailTransport,
+    /// The directory of the `file` transport
+    pub dir: String,
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            transport: MailTransport::default(),
+            dir: "mail".to_string(),
+        }
+    }
+}
+
+/// Where the emails go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MailTransport {
+    /// Written to the server logs
+    #[default]
+    Log,
+    /// Written to files in the mail directory
+    File,
+}
+
+/// The settings of the logs.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// How the log lines are written
+    pub format: LogFormat,
+}
+
+impl AppConfig {
+    /// Loads the configuration: the TOML file named by `CONFIG_FILE` (or
+    /// `config.toml` if it exists) is read first, then the environment variables
+    /// override its settings, and the result is validated.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerStartingError` listing every problem if the file
+    /// cannot be read or parsed, a variable cannot be parsed, or a setting is not valid.
+    pub fn load() -> Result<Self, ErrorType> {
+        let mut config = Self::from_file()?;
+        let mut problems = config.apply_env();
+        problems.extend(config.problems());
+
+        if !problems.is_empty() {
+            return Err(ErrorType::ServerStartingError(format!(
+                "The configuration is not valid: {}",
+                problems.join("; ")
+            )));
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the TOML file named by `CONFIG_FILE`, or `config.toml` if it exists, or
+    /// returns the default configuration.
+    fn from_file() -> Result<Self, ErrorType> {
+        let path = match env::var("CONFIG_FILE") {
+            Ok(path) => path,
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE.to_string(),
+            Err(_) => return Ok(Self::default()),
+        };
+
+        let contents = std::fs::read_to_string(&path).map_err(|err| {
+            ErrorType::ServerStartingError(format!(
+                "The configuration file {} cannot be read: {}",
+                path, err
+            ))
+        })?;
+        toml::from_str(&contents).map_err(|err| {
+            ErrorType::ServerStartingError(format!(
+                "The configuration file {} is not valid: {}",
+                path, err
+            ))
+        })
+    }
+
+    /// Overrides the settings with the environment variables that are set,
+    /// returning the variables that cannot be parsed.
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        override_parsed(&mut self.storage, "STORAGE_BACKEND", &mut problems);
+        override_parsed(&mut self.server.host, "HOST", &mut problems);
+        override_parsed(&mut self.server.port, "PORT", &mut problems);
+        override_parsed(
+            &mut self.server.health_check_timeout_ms,
+            "HEALTH_CHECK_TIMEOUT_MS",
+            &mut problems,
+        );
+        override_parsed(
+            &mut self.server.shutdown_timeout_secs,
+            "SHUTDOWN_TIMEOUT_SECS",
+            &mut problems,
+        );
+
+        if let Ok(uri) = env::var("MONGO_URI") {
+            self.database.uri = Some(uri);
+        }
+        override_parsed(&mut self.database.name, "DATABASE_NAME", &mut problems);
+        override_parsed(
+            &mut self.database.min_pool_size,
+            "DB_MIN_POOL_SIZE",
+            &mut problems,
+        );
+        override_parsed(
+            &mut self.database.max_pool_size,
+            "DB_MAX_POOL_SIZE",
+            &mut problems,
+        );
+        override_parsed(
+            &mut self.database.connect_timeout_secs,
+            "DB_CONNECT_TIMEOUT_SECS",
+            &mut problems,
+        );
+        override_parsed(
+            &mut self.database.server_selection_timeout_secs,
+            "DB_SERVER_SELECTION_TIMEOUT_SECS",
+            &mut problems,
+        );
+        override_parsed(
+            &mut self.database.migrate_on_startup,
+            "MIGRATE_ON_STARTUP",
+            &mut problems,
+        );
+
+        override_parsed(&mut self.auth.jwt_secret, "JWT_SECRET", &mut problems);
+        if let Ok(email) = env::var("ADMIN_EMAIL") {
+            self.auth.admin_email = Some(email);
+        }
+        if let Ok(password) = env::var("ADMIN_PASSWORD") {
+            self.auth.admin_password = Some(password);
+        }
+
+        override_parsed(&mut self.mail.transport, "MAIL_TRANSPORT", &mut problems);
+        override_parsed(&mut self.mail.dir, "MAIL_DIR", &mut problems);
+        override_parsed(&mut self.log.format, "LOG_FORMAT", &mut problems);
+
+        problems
+    }
+
+    /// Returns every setting that is not valid, with the reason.
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        require(
+            &mut problems,
+            !self.server.host.trim().is_empty(),
+            "the host must not be empty",
+        );
+        require(
+            &mut problems,
+            self.server.port != 0,
+            "the port must not be 0",
+        );
+        require(
+            &mut problems,
+            self.server.health_check_timeout_ms > 0,
+            "the health check timeout must be positive",
+        );
+        require(
+            &mut problems,
+            self.server.shutdown_timeout_secs > 0,
+            "the shutdown timeout must be positive",
+        );
+
+        if self.storage == StorageBackend::Mongo {
+            require(
+                &mut problems,
+                self.database
+                    .uri
+                    .as_deref()
+                    .is_some_and(|uri| !uri.trim().is_empty()),
+                "MONGO_URI is required unless the storage is `memory`",
+            );
+        }
+        require(
+            &mut problems,
+            !self.database.name.trim().is_empty(),
+            "the database name must not be empty",
+        );
+        require(
+            &mut problems,
+            self.database.max_pool_size > 0,
+            "the maximum pool size must be positive",
+        );
+        require(
+            &mut problems,
+            self.database.min_pool_size <= self.database.max_pool_size,
+            "the minimum pool size must not exceed the maximum pool size",
+        );
+        require(
+            &mut problems,
+            self.database.connect_timeout_secs > 0,
+            "the connect timeout must be positive",
+        );
+        require(
+            &mut problems,
+            self.database.server_selection_timeout_secs > 0,
+            "the server selection timeout must be positive",
+        );
+
+        let collections = self.database.collections.all();
+        for (index, (setting, name)) in collections.iter().enumerate() {
+            if name.trim().is_empty() {
+                problems.push(format!("the {} collection name must not be empty", setting));
+            } else if let Some((other, _)) = collections[..index]
+                .iter()
+                .find(|(_, other_name)| other_name == name)
+            {
+                problems.push(format!(
+                    "the {} and {} collections must have different names",
+                    other, setting
+                ));
+            }
+        }
+
+        require(
+            &mut problems,
+            !self.auth.jwt_secret.is_empty(),
+            "JWT_SECRET is required",
+        );
+        require(
+            &mut problems,
+            self.auth.admin_email.is_some() == self.auth.admin_password.is_some(),
+            "ADMIN_EMAIL and ADMIN_PASSWORD must be set together",
+        );
+
+        problems
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mongo" => Ok(StorageBackend::Mongo),
+            "memory" => Ok(StorageBackend::Memory),
+            _ => Err("expected `mongo` or `memory`".to_string()),
+        }
+    }
+}
+
+impl FromStr for MailTransport {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "log" => Ok(MailTransport::Log),
+            "file" => Ok(MailTransport::File),
+            _ => Err("expected `log` or `file`".to_string()),
+        }
+    }
+}
+
+/// Replaces a setting with the parsed value of an environment variable, if it is
+/// set, recording a problem if it cannot be parsed.
+fn override_parsed<T>(setting: &mut T, variable: &str, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Ok(value) = env::var(variable) else {
+        return;
+    };
+
+    match value.parse() {
+        Ok(parsed) => *setting = parsed,
+        Err(err) => problems.push(format!("{} is not valid ({})", variable, err)),
+    }
+}
+
+/// Records a problem unless the condition holds.
+fn require(problems: &mut Vec<String>, condition: bool, problem: &str) {
+    if !condition {
+        problems.push(problem.to_string());
+    }
+}
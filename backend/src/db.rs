@@ -1,22 +1,55 @@
 //! This module handles the database connection.
 
-use mongodb::{Client, options::ClientOptions};
-use std::env;
+use crate::config::DatabaseConfig;
+use mongodb::{
+    Client,
+    error::{ErrorKind, InsertManyError, WriteFailure},
+    options::ClientOptions,
+};
 
-/// This function reads the MongoDB URI from the `MONGO_URI` environment variable,
-/// parses it, and creates a new MongoDB client.
+/// The server error code of a write rejected by a unique index.
+const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// This function parses the MongoDB URI of the configuration, applies the pool sizes
+/// and timeouts of the configuration, and creates a new MongoDB client.
+///
+/// # Arguments
+///
+/// * `config` - The database configuration, whose URI has been checked to be set.
 ///
 /// # Returns
 ///
 /// A `Result` containing the MongoDB `Client` on success, or a `mongodb::error::Error` on failure.
-pub async fn connect_to_database() -> Result<Client, mongodb::error::Error> {
-    let db_uri = env::var("MONGO_URI").expect("There is no MongoDB URI in .env");
+pub async fn connect_to_database(config: &DatabaseConfig) -> Result<Client, mongodb::error::Error> {
+    let db_uri = config.uri.as_deref().unwrap_or_default();
 
     // Parse the MongoDB URI to obtain client options.
-    let client_options = ClientOptions::parse(&db_uri).await?;
+    let mut client_options = ClientOptions::parse(db_uri).await?;
+    client_options.min_pool_size = Some(config.min_pool_size);
+    client_options.max_pool_size = Some(config.max_pool_size);
+    client_options.connect_timeout = Some(config.connect_timeout());
+    client_options.server_selection_timeout = Some(config.server_selection_timeout());
 
     // Connects lazily, actual connection happens on first DB use.
     let client = Client::with_options(client_options)?;
 
     Ok(client)
-}
\ No newline at end of file
+}
+
+/// This function tells whether a MongoDB error was caused by a unique index
+/// rejecting a duplicate key.
+pub fn is_duplicate_key_error(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_CODE
+        }
+        ErrorKind::InsertMany(InsertManyError {
+            write_errors: Some(write_errors),
+            ..
+        }) => write_errors
+            .iter()
+            .any(|write_error| write_error.code == DUPLICATE_KEY_CODE),
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
@@ -1,39 +1,134 @@
 //! This module defines the custom error types for the application.
 
+use crate::request_id::current_request_id;
 use axum::{
     Json,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use serde::Serialize;
+use tracing::{error, info};
+
+/// Describes why a single field of a request is not valid.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// The name of the field, as it appears in the request
+    pub field: String,
+    /// What is wrong with the value of the field
+    pub message: String,
+}
 
 /// Represents the possible errors that can occur in the application.
 #[derive(Debug)]
 pub enum ErrorType {
     /// Returned when a field already exists.
     AlreadyExists(String),
+    /// Returned when a record is changed through an archived class.
+    Archived(String),
     /// Returned when a field is not found.
     DoesNotExist(String),
-    /// Returned when trying to create a student with a negative roll number.
-    NegativeRollNumber(String),
+    /// Returned when the caller is authenticated but their role does not allow the request.
+    Forbidden(String),
+    /// Returned when a record cannot be removed because other records still refer to it.
+    InUse(String),
+    /// Returned when the query parameters or the body of a request are not valid.
+    InvalidInput(String),
+    /// Returned when the route exists but does not accept the method of the request.
+    MethodNotAllowed(String),
+    /// Returned when a student is not enrolled in the class they are marked for.
+    NotEnrolled(String),
     /// Returned for generic server errors.
     ServerError(String),
     /// Returned for failure of server starting.
     ServerStartingError(String),
+    /// Returned when the request has no valid bearer token or the credentials are wrong.
+    Unauthorized(String),
+    /// Returned when the body of a request is not sent as JSON.
+    UnsupportedMediaType(String),
+    /// Returned when one or more fields of a model are not valid, listing every one of them.
+    Validation(Vec<FieldError>),
+}
+
+impl ErrorType {
+    /// Returns the stable, machine-readable code of the error, which clients can
+    /// match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorType::AlreadyExists(_) => "already_exists",
+            ErrorType::Archived(_) => "archived",
+            ErrorType::DoesNotExist(_) => "not_found",
+            ErrorType::Forbidden(_) => "forbidden",
+            ErrorType::InUse(_) => "in_use",
+            ErrorType::InvalidInput(_) => "invalid_input",
+            ErrorType::MethodNotAllowed(_) => "method_not_allowed",
+            ErrorType::NotEnrolled(_) => "not_enrolled",
+            ErrorType::ServerError(_) => "server_error",
+            ErrorType::ServerStartingError(_) => "server_starting_error",
+            ErrorType::Unauthorized(_) => "unauthorized",
+            ErrorType::UnsupportedMediaType(_) => "unsupported_media_type",
+            ErrorType::Validation(_) => "validation_failed",
+        }
+    }
+
+    /// Returns the HTTP status code of the error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorType::AlreadyExists(_) => StatusCode::CONFLICT,
+            ErrorType::Archived(_) => StatusCode::CONFLICT,
+            ErrorType::DoesNotExist(_) => StatusCode::NOT_FOUND,
+            ErrorType::Forbidden(_) => StatusCode::FORBIDDEN,
+            ErrorType::InUse(_) => StatusCode::CONFLICT,
+            ErrorType::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            ErrorType::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorType::NotEnrolled(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorType::ServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorType::ServerStartingError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorType::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ErrorType::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorType::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
 }
 
 impl IntoResponse for ErrorType {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            ErrorType::AlreadyExists(msg) => (StatusCode::CONFLICT, msg),
-            ErrorType::DoesNotExist(msg) => (StatusCode::NOT_FOUND, msg),
-            ErrorType::NegativeRollNumber(msg) => (StatusCode::BAD_REQUEST, msg),
-            ErrorType::ServerError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            ErrorType::ServerStartingError(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+        let status = self.status();
+        let code = self.code();
+        let request_id = current_request_id();
+
+        let (error_message, details) = match self {
+            ErrorType::AlreadyExists(msg)
+            | ErrorType::Archived(msg)
+            | ErrorType::DoesNotExist(msg)
+            | ErrorType::Forbidden(msg)
+            | ErrorType::InUse(msg)
+            | ErrorType::InvalidInput(msg)
+            | ErrorType::MethodNotAllowed(msg)
+            | ErrorType::NotEnrolled(msg)
+            | ErrorType::ServerError(msg)
+            | ErrorType::ServerStartingError(msg)
+            | ErrorType::Unauthorized(msg)
+            | ErrorType::UnsupportedMediaType(msg) => (msg, None),
+            ErrorType::Validation(fields) => (
+                "The request is not valid".to_string(),
+                Some(serde_json::json!({ "fields": fields })),
+            ),
         };
 
-        let body = serde_json::json!({
-            "error": error_message
+        if status.is_server_error() {
+            error!(code, "{}", error_message);
+        } else {
+            info!(code, "{}", error_message);
+        }
+
+        let mut body = serde_json::json!({
+            "code": code,
+            "error": error_message,
+            "request_id": request_id
         });
+        if let Some(details) = details {
+            body["details"] = details;
+        }
         (status, Json(body)).into_response()
     }
 }
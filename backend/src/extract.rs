@@ -0,0 +1,169 @@
+//! This module defines the extractors used by the routes in place of axum's `Path`,
+//! `Query` and `Json`. They extract the same values, but a request they cannot
+//! extract is rejected with an `ErrorType`, so that malformed IDs, query strings and
+//! bodies get the same JSON error shape as every other error.
+
+use crate::error::{ErrorType, FieldError};
+use axum::{
+    extract::{
+        FromRequest, FromRequestParts, Request,
+        path::ErrorKind,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::error::Error;
+use tracing::error;
+
+/// Extracts the parameters of the path, like `axum::extract::Path`.
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ErrorType;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) = axum::extract::Path::from_request_parts(parts, state)
+            .await
+            .map_err(path_error)?;
+        Ok(Path(value))
+    }
+}
+
+/// Extracts the query string, like `axum::extract::Query`.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ErrorType;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) = axum::extract::Query::from_request_parts(parts, state)
+            .await
+            .map_err(query_error)?;
+        Ok(Query(value))
+    }
+}
+
+/// Extracts a JSON body, like `axum::Json`. It is also returned as a JSON response.
+#[derive(Debug)]
+pub struct Json<T>(pub T);
+
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ErrorType;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::from_request(request, state)
+            .await
+            .map_err(json_error)?;
+        Ok(Json(value))
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Turns a path that cannot be parsed into an error naming the faulty parameter.
+fn path_error(rejection: PathRejection) -> ErrorType {
+    let PathRejection::FailedToDeserializePathParams(error) = rejection else {
+        error!(error = %rejection.body_text(), "Error extracting the path");
+        return ErrorType::ServerError("Server Error".to_string());
+    };
+
+    match error.into_kind() {
+        ErrorKind::ParseErrorAtKey { key, value, .. }
+        | ErrorKind::DeserializeError { key, value, .. } => {
+            ErrorType::InvalidInput(format!("`{}` is not a valid {}", value, key))
+        }
+        ErrorKind::ParseError { value, .. } | ErrorKind::ParseErrorAtIndex { value, .. } => {
+            ErrorType::InvalidInput(format!("`{}` is not a valid path parameter", value))
+        }
+        ErrorKind::InvalidUtf8InPathParam { key } => {
+            ErrorType::InvalidInput(format!("The {} is not valid UTF-8", key))
+        }
+        ErrorKind::Message(message) => {
+            ErrorType::InvalidInput(format!("The path is not valid: {}", message))
+        }
+        kind => {
+            error!(error = %kind, "Error extracting the path");
+            ErrorType::ServerError("Server Error".to_string())
+        }
+    }
+}
+
+/// Turns a query string that cannot be parsed into an error explaining why.
+fn query_error(rejection: QueryRejection) -> ErrorType {
+    match rejection {
+        QueryRejection::FailedToDeserializeQueryString(error) => ErrorType::InvalidInput(format!(
+            "The query string is not valid: {}",
+            source_message(&error)
+        )),
+        rejection => ErrorType::InvalidInput(rejection.body_text()),
+    }
+}
+
+/// Turns a body that cannot be parsed into an error explaining why. A body of the
+/// wrong shape is reported like a validation error, naming the faulty field.
+fn json_error(rejection: JsonRejection) -> ErrorType {
+    match rejection {
+        JsonRejection::JsonDataError(error) => {
+            let message = source_message(&error);
+            // The message reads `field.path: what is wrong`, or only names the field
+            // when a top-level field is missing
+            let missing_field = message
+                .strip_prefix("missing field `")
+                .and_then(|rest| rest.split_once('`'));
+            let field_error = match (missing_field, message.split_once(": ")) {
+                (Some((field, _)), _) => FieldError {
+                    field: field.to_string(),
+                    message: "is required".to_string(),
+                },
+                (None, Some((field, reason))) if !field.contains(' ') => FieldError {
+                    field: field.to_string(),
+                    message: reason.to_string(),
+                },
+                _ => FieldError {
+                    field: "body".to_string(),
+                    message,
+                },
+            };
+            ErrorType::Validation(vec![field_error])
+        }
+        JsonRejection::JsonSyntaxError(error) => ErrorType::InvalidInput(format!(
+            "The body is not valid JSON: {}",
+            source_message(&error)
+        )),
+        JsonRejection::MissingJsonContentType(_) => ErrorType::UnsupportedMediaType(
+            "The body must be sent with `Content-Type: application/json`".to_string(),
+        ),
+        rejection => ErrorType::InvalidInput(rejection.body_text()),
+    }
+}
+
+/// Returns the message of the error that caused a rejection, without axum's prefix.
+fn source_message(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message = error.to_string();
+        source = error.source();
+    }
+    message
+}
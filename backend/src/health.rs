@@ -0,0 +1,44 @@
+//! This module defines the health checks of the dependencies the server needs to
+//! answer requests, which the readiness probe runs.
+
+use async_trait::async_trait;
+use mongodb::{Database, bson::doc};
+
+/// Checks that a dependency of the server is reachable.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// The name of the dependency, as reported by the readiness probe.
+    fn name(&self) -> &'static str;
+
+    /// Checks the dependency, returning why it is not reachable on failure.
+    async fn check(&self) -> Result<(), String>;
+}
+
+/// A `HealthCheck` pinging a MongoDB database.
+pub struct MongoHealthCheck {
+    database: Database,
+}
+
+impl MongoHealthCheck {
+    /// Creates a health check pinging the given database.
+    pub fn new(database: &Database) -> Self {
+        Self {
+            database: database.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthCheck for MongoHealthCheck {
+    fn name(&self) -> &'static str {
+        "mongodb"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        self.database
+            .run_command(doc! { "ping": 1 })
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
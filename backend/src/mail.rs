@@ -0,0 +1,100 @@
+//! This module defines the transport of the emails sent by the application, such as
+//! the password-reset emails. The transport is chosen at startup, so that a real
+//! mail service can be plugged in without changing the routes.
+
+use crate::config::{MailConfig, MailTransport};
+use crate::error::ErrorType;
+use async_trait::async_trait;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::{path::PathBuf, sync::Arc};
+use tracing::{error, info};
+
+/// An email to send.
+#[derive(Debug, Clone)]
+pub struct Email {
+    /// The address of the recipient
+    pub to: String,
+    /// The subject line
+    pub subject: String,
+    /// The plain-text body
+    pub body: String,
+}
+
+/// Abstracts the sending of emails, so that the routes do not depend on a
+/// particular mail service.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    /// Sends an email.
+    async fn send(&self, email: &Email) -> Result<(), ErrorType>;
+}
+
+/// A `Mailer` that writes the emails to the server logs instead of sending them.
+/// It is used for local testing.
+#[derive(Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), ErrorType> {
+        info!(
+            to = %email.to,
+            subject = %email.subject,
+            body = %email.body,
+            "Email"
+        );
+        Ok(())
+    }
+}
+
+/// A `Mailer` that writes each email to its own file in a directory instead of
+/// sending it. It is used for local testing.
+pub struct FileMailer {
+    directory: PathBuf,
+}
+
+impl FileMailer {
+    /// Creates a mailer writing to the given directory, which is created when the
+    /// first email is written.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> Result<(), ErrorType> {
+        let file_error = |err: std::io::Error| {
+            error!(error = %err, "Error writing email");
+            ErrorType::ServerError("Server Error".to_string())
+        };
+
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .map_err(file_error)?;
+
+        let path = self.directory.join(format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            ObjectId::new().to_hex()
+        ));
+        let contents = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+
+        tokio::fs::write(path, contents).await.map_err(file_error)
+    }
+}
+
+/// Creates the mailer selected by the configuration: the `file` transport writes the
+/// emails to the mail directory, and the `log` transport writes them to the server
+/// logs.
+pub fn mailer_from_config(config: &MailConfig) -> Arc<dyn Mailer> {
+    match config.transport {
+        MailTransport::File => Arc::new(FileMailer::new(config.dir.clone())),
+        MailTransport::Log => Arc::new(LogMailer),
+    }
+}
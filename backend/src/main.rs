@@ -1,56 +1,225 @@
 //! This is the main entry point of the application.
 
+mod auth;
+mod cli;
+mod config;
 mod db;
+mod extract;
+mod health;
+mod mail;
+mod migration;
+mod request_id;
+mod schema;
+mod shutdown;
 mod state;
+mod telemetry;
+mod transaction;
+mod validation;
 mod routes {
     pub mod attendance_route;
+    pub mod auth_route;
     pub mod class_route;
+    pub mod enrollment_route;
+    pub mod health_route;
+    pub mod session_route;
     pub mod student_route;
     pub mod teacher_route;
+    pub mod user_route;
+}
+mod migrations {
+    pub mod m001_attendance_status;
+    pub mod m002_teacher_classes;
 }
 mod models {
     pub mod attendance_model;
     pub mod class_model;
+    pub mod enrollment_model;
+    pub mod health_model;
+    pub mod page_model;
+    pub mod session_model;
     pub mod student_model;
     pub mod teacher_model;
+    pub mod user_model;
+}
+mod repositories {
+    pub mod attendance_repository;
+    pub mod class_repository;
+    pub mod enrollment_repository;
+    pub mod password_reset_repository;
+    pub mod session_repository;
+    pub mod student_repository;
+    pub mod teacher_repository;
+    pub mod user_repository;
 }
 mod error;
+#[cfg(test)]
+mod tests;
 
+use crate::auth::TokenKeys;
+use crate::cli::Command;
+use crate::config::{AppConfig, StorageBackend};
+use crate::migration::Migrator;
 use crate::routes::{
-    attendance_route::{get_attendance_by_class, get_attendance_by_student, mark_attendance},
-    class_route::{add_class, get_classes},
-    student_route::{add_student, get_student},
-    teacher_route::{add_teacher, get_teacher},
+    attendance_route::{
+        get_attendance_by_class, get_attendance_by_student, mark_attendance, roll_call,
+    },
+    auth_route::{change_password, get_me, login, request_password_reset, reset_password},
+    class_route::{
+        add_class, archive_class, delete_class, get_class, get_classes, unarchive_class,
+        update_class,
+    },
+    enrollment_route::{enroll_student, get_roster, unenroll_student},
+    health_route::{get_liveness, get_readiness},
+    session_route::{add_session, delete_session, get_session, get_sessions, update_session},
+    student_route::{
+        add_student, delete_student, get_student, get_student_summary, get_students, update_student,
+    },
+    teacher_route::{
+        add_teacher, assign_class, delete_teacher, get_teacher, get_teachers, unassign_class,
+        update_teacher,
+    },
+    user_route::add_user,
 };
+use crate::shutdown::Shutdown;
 use crate::state::AppState;
 use axum::{
-    Extension, Router,
+    Extension, Router, middleware,
     routing::{get, post},
 };
 use dotenvy::dotenv;
 use error::ErrorType;
-use std::{net::SocketAddr, sync::Arc};
+use mongodb::Client;
 use tokio::net::TcpListener;
+use tower_http::trace::TraceLayer;
+use tracing::{error, info, warn};
 
-/// This function initializes the application by loading the environment variables,
+/// This function initializes the application by loading the configuration,
 /// connecting to the database, creating the application state, and starting the
-/// HTTP server.
+/// HTTP server, which runs until the process receives SIGINT or SIGTERM.
+///
+/// The configuration is read from the optional `config.toml` file (or the file named
+/// by `CONFIG_FILE`) and the environment variables, including those of a `.env` file
+/// if there is one (see `config::AppConfig::load`). Setting `STORAGE_BACKEND=memory`
+/// runs the server against in-memory repositories instead of MongoDB.
+///
+/// With MongoDB, the server refuses to start while a migration is pending, unless
+/// `MIGRATE_ON_STARTUP` is set (see `migration::Migrator`). Running the binary with
+/// `--migrate` applies the pending migrations and exits instead of serving, and
+/// adding `--dry-run` only lists them.
 #[tokio::main]
 pub async fn main() -> Result<(), ErrorType> {
-    dotenv().expect(".env does not exist");
+    // A missing .env file is not an error: the settings may come from the
+    // environment or the configuration file.
+    dotenv().ok();
+    let command = Command::from_args(std::env::args().skip(1))?;
+    let config = AppConfig::load()?;
+    telemetry::init_tracing(config.log.format);
+
+    if let Command::Migrate { dry_run } = command {
+        return migrate(&config, dry_run).await;
+    }
+
+    let tokens = TokenKeys::from_secret(config.auth.jwt_secret.as_bytes());
+    let mailer = mail::mailer_from_config(&config.mail);
+    let address = format!("{}:{}", config.server.host, config.server.port);
+
+    let mut mongo_client = None;
+    let app_state = match config.storage {
+        StorageBackend::Memory => AppState::in_memory(config, tokens, mailer),
+        StorageBackend::Mongo => {
+            let client = connect(&config).await?;
+            let database = client.database(&config.database.name);
+            let migrator = Migrator::new(&database, &config.database.collections);
+            if config.database.migrate_on_startup {
+                migrator.migrate(false).await?;
+            } else {
+                migrator.check().await?;
+            }
+
+            let app_state = AppState::mongo(&client, config, tokens, mailer)
+                .await
+                .map_err(|_| {
+                    ErrorType::ServerStartingError("The database cannot be prepared".to_string())
+                })?;
+            mongo_client = Some(client);
+            app_state
+        }
+    };
+
+    let auth_config = &app_state.config.auth;
+    if let (Some(email), Some(password)) = (&auth_config.admin_email, &auth_config.admin_password) {
+        auth::ensure_admin(&app_state, email, password)
+            .await
+            .map_err(|_| {
+                ErrorType::ServerStartingError("The admin account cannot be created".to_string())
+            })?;
+    }
+
+    let shutdown_timeout = app_state.config.server.shutdown_timeout();
+
+    let app = router(app_state);
+
+    let listener = TcpListener::bind(&address).await.map_err(|err| {
+        error!(error = %err, "Error binding to address");
+        ErrorType::ServerStartingError(format!("Server failed to bind to {}: {}", address, err))
+    })?; // Establishes the TCP listener to handle incoming requests.
 
-    let client = db::connect_to_database().await.map_err(|err| {
-        println!("Error starting the server: {}", err);
-        ErrorType::ServerStartingError("Server failed to start".to_string())
-    })?;
-    let shared_client = Arc::new(client);
+    info!(%address, "Listening");
 
-    let app_state = AppState {
-        db_client: shared_client.clone(),
+    // Combines the router and the listener, and serves HTTP requests until SIGINT or
+    // SIGTERM. The server then stops accepting connections and waits for the
+    // in-flight requests, up to the shutdown deadline.
+    let shutdown = Shutdown::on_signal();
+    let server = axum::serve(listener, app).with_graceful_shutdown({
+        let mut shutdown = shutdown.clone();
+        async move { shutdown.requested().await }
+    });
+    let deadline = {
+        let mut shutdown = shutdown.clone();
+        async move {
+            shutdown.requested().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        }
     };
 
-    let app = Router::new()
+    let drained = tokio::select! {
+        result = server => {
+            result.map_err(|err| {
+                error!(error = %err, "Error serving");
+                ErrorType::ServerStartingError("Server failed to serve".to_string())
+            })?;
+            true
+        }
+        _ = deadline => {
+            warn!("The in-flight requests did not finish before the shutdown deadline");
+            false
+        }
+    };
+
+    // Requests cut off by the deadline may still hold database handles, which a
+    // graceful close of the client would wait for.
+    if let Some(client) = mongo_client {
+        client.shutdown().immediate(!drained).await;
+        info!("Closed the MongoDB connections");
+    }
+
+    info!("Stopped");
+    Ok(())
+}
+
+/// This function builds the router of the API: every route, the fallbacks for the
+/// unknown paths and methods, and the layers injecting the application state,
+/// logging the requests and giving each one an ID.
+fn router(app_state: AppState) -> Router {
+    Router::new()
         .route("/", get(root_handler))
+        .route("/health/live", get(get_liveness))
+        .route("/health/ready", get(get_readiness))
+        .route("/auth/login", post(login))
+        .route("/auth/me", get(get_me))
+        .route("/auth/password", post(change_password))
+        .route("/auth/password/forgot", post(request_password_reset))
+        .route("/auth/password/reset", post(reset_password))
         .route("/attendance/mark", post(mark_attendance))
         .route(
             "/attendance/students/{student_id}",
@@ -62,40 +231,127 @@ pub async fn main() -> Result<(), ErrorType> {
         )
         .route("/classes", get(get_classes))
         .route("/classes/add", post(add_class))
+        .route(
+            "/classes/{class_id}",
+            get(get_class).patch(update_class).delete(delete_class),
+        )
+        .route("/classes/{class_id}/archive", post(archive_class))
+        .route("/classes/{class_id}/unarchive", post(unarchive_class))
+        .route("/classes/{class_id}/enroll", post(enroll_student))
+        .route("/classes/{class_id}/unenroll", post(unenroll_student))
+        .route("/classes/{class_id}/roster", get(get_roster))
+        .route("/classes/{class_id}/sessions", get(get_sessions))
+        .route("/classes/{class_id}/sessions/add", post(add_session))
+        .route(
+            "/classes/{class_id}/sessions/{session_id}",
+            get(get_session)
+                .patch(update_session)
+                .delete(delete_session),
+        )
+        .route(
+            "/classes/{class_id}/sessions/{session_id}/rollcall",
+            post(roll_call),
+        )
+        .route("/students", get(get_students))
         .route("/students/add", post(add_student))
-        .route("/students/{student_id}", get(get_student))
+        .route(
+            "/students/{student_id}",
+            get(get_student)
+                .patch(update_student)
+                .delete(delete_student),
+        )
+        .route("/students/{student_id}/summary", get(get_student_summary))
+        // Deprecated aliases of `/teachers/add` and `/teachers/{teacher_id}`, kept for
+        // existing clients
         .route("/teacher/add", post(add_teacher))
         .route("/teacher/{teacher_id}", get(get_teacher))
-        .layer(Extension(app_state)); // Injects the application state into all routes.
-
-    let address = SocketAddr::from(([127, 0, 0, 1], 3000)); // Defines the IP address and port explicitly.
-    let listener = TcpListener::bind(address).await.map_err(|err| {
-        println!("Error binding to address: {}", err);
-        ErrorType::ServerStartingError("Server failed to bind".to_string())
-    })?; // Establishes the TCP listener to handle incoming requests.
+        .route("/teachers", get(get_teachers))
+        .route("/teachers/add", post(add_teacher))
+        .route(
+            "/teachers/{teacher_id}",
+            get(get_teacher)
+                .patch(update_teacher)
+                .delete(delete_teacher),
+        )
+        .route(
+            "/teachers/{teacher_id}/classes/{class_id}",
+            post(assign_class).delete(unassign_class),
+        )
+        .route("/users/add", post(add_user))
+        .fallback(route_not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .layer(Extension(app_state)) // Injects the application state into all routes.
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::make_request_span)
+                .on_response(telemetry::on_response),
+        ) // Logs every request with its method, path, status and latency.
+        .layer(middleware::from_fn(request_id::request_id_layer)) // Gives every request an ID.
+}
 
-    println!("listening on {}", address);
-    
-    axum::serve(listener, app).await.map_err(|err| {
-        println!("Error serving: {}", err);
-        ErrorType::ServerStartingError("Server failed to serve".to_string())
-    })?; // Combines the router and the listener, and starts serving HTTP requests.
+/// This function connects to MongoDB with the database settings of the configuration.
+///
+/// # Errors
+///
+/// Returns `ErrorType::ServerStartingError` if the client cannot be created.
+async fn connect(config: &AppConfig) -> Result<Client, ErrorType> {
+    db::connect_to_database(&config.database)
+        .await
+        .map_err(|err| {
+            error!(error = %err, "Error starting the server");
+            ErrorType::ServerStartingError(format!("The database cannot be reached: {}", err))
+        })
+}
 
+/// This function applies the pending migrations of the MongoDB database, or only lists
+/// them on a dry run, for `backend --migrate`.
+///
+/// # Errors
+///
+/// Returns `ErrorType::ServerStartingError` if the storage is not MongoDB, the
+/// database cannot be reached, or a migration fails.
+async fn migrate(config: &AppConfig, dry_run: bool) -> Result<(), ErrorType> {
+    if config.storage != StorageBackend::Mongo {
+        return Err(ErrorType::ServerStartingError(
+            "Migrations only apply to the `mongo` storage".to_string(),
+        ));
+    }
 
-    Ok(())
+    let client = connect(config).await?;
+    let database = client.database(&config.database.name);
+    let result = Migrator::new(&database, &config.database.collections)
+        .migrate(dry_run)
+        .await;
+    client.shutdown().await;
+    result
 }
 
 /// This function is the handler for the root route of the application. It returns a
 /// simple string to indicate that the backend is running.
 ///
-/// # Arguments
-///
-/// * `state` - The application state, which contains the database client.
-///
 /// # Returns
 ///
 /// A static string message 'Attendance portal backend is running'.
-async fn root_handler(Extension(state): Extension<AppState>) -> &'static str {
-    let _ = &state.db_client;
+async fn root_handler() -> &'static str {
     "Attendance portal backend is running"
 }
+
+/// This function is the handler for the paths that match no route.
+///
+/// # Returns
+///
+/// Always an `ErrorType::DoesNotExist`, so that unknown paths get the same JSON error
+/// shape as every other error.
+async fn route_not_found() -> ErrorType {
+    ErrorType::DoesNotExist("The route does not exist".to_string())
+}
+
+/// This function is the handler for the requests whose method is not accepted by the
+/// route of their path.
+///
+/// # Returns
+///
+/// Always an `ErrorType::MethodNotAllowed`.
+async fn method_not_allowed() -> ErrorType {
+    ErrorType::MethodNotAllowed("The route does not accept this method".to_string())
+}
@@ -0,0 +1,328 @@
+//! This module defines the versioned migrations of the MongoDB data. Each migration
+//! is a Rust type with a version; the versions applied to a database are recorded in
+//! the `_migrations` collection, and the migrations still pending are applied in
+//! order, either at startup (`MIGRATE_ON_STARTUP`) or with `backend --migrate`.
+//!
+//! The server refuses to start while a migration is pending, or when the database
+//! was migrated by a newer version of the server, so that the models never read data
+//! of a shape they do not expect. A new database, holding no data yet, has every
+//! migration recorded as applied the first time it is checked or migrated.
+
+use crate::config::CollectionNames;
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::migrations::{
+    m001_attendance_status::AttendanceStatusMigration,
+    m002_teacher_classes::TeacherClassesMigration,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    Collection, Database,
+    bson::{Document, doc},
+};
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+use tracing::{error, info, instrument};
+
+/// A change to the shape of the stored data.
+#[async_trait]
+pub trait Migration: Send + Sync {
+    /// The version of the migration. The migrations are applied in the order of
+    /// their versions, which never change once released.
+    fn version(&self) -> u32;
+
+    /// A short name describing the migration.
+    fn name(&self) -> &'static str;
+
+    /// Counts the documents the migration would change, for a dry run.
+    async fn pending(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error>;
+
+    /// Applies the migration and returns how many documents it changed. A migration
+    /// interrupted halfway is applied again from the start, so it must only change
+    /// the documents still having the old shape.
+    async fn apply(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error>;
+}
+
+/// Returns every migration, in the order of their versions.
+fn migrations() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(AttendanceStatusMigration),
+        Box::new(TeacherClassesMigration),
+    ]
+}
+
+/// The record of a migration applied to the database.
+#[derive(Debug, Serialize, Deserialize)]
+struct AppliedMigration {
+    #[serde(rename = "_id")]
+    version: u32,
+    name: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    applied_at: DateTime<Utc>,
+    changed: u64,
+}
+
+/// Applies the pending migrations to a database and checks that it is up to date.
+pub struct Migrator<'a> {
+    database: &'a Database,
+    collections: &'a CollectionNames,
+    applied: Collection<AppliedMigration>,
+    migrations: Vec<Box<dyn Migration>>,
+}
+
+impl<'a> Migrator<'a> {
+    /// Creates a migrator of the given database, recording the applied migrations in
+    /// the `migrations` collection, `_migrations` by default.
+    pub fn new(database: &'a Database, collections: &'a CollectionNames) -> Self {
+        Self {
+            database,
+            collections,
+            applied: database.collection(&collections.migrations),
+            migrations: migrations(),
+        }
+    }
+
+    /// Checks that every migration has been applied, after recording them all as
+    /// applied if the database is new.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerStartingError` if a migration is pending, if the
+    /// applied migrations do not match those of this version of the server, or if
+    /// the database cannot be read.
+    pub async fn check(&self) -> Result<(), ErrorType> {
+        if self.initialise(false).await? {
+            return Ok(());
+        }
+
+        let pending = self.pending().await?;
+        if let Some(first) = pending.first() {
+            return Err(ErrorType::ServerStartingError(format!(
+                "The database is missing {} migration(s), starting with {} ({}); run \
+                 `backend --migrate` or set MIGRATE_ON_STARTUP=true",
+                pending.len(),
+                first.version(),
+                first.name()
+            )));
+        }
+
+        info!("The database is up to date");
+        Ok(())
+    }
+
+    /// Applies the pending migrations in order, recording each one once it is
+    /// applied. A dry run only logs the migrations it would apply, with the number
+    /// of documents each would change.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::ServerStartingError` if the applied migrations do not
+    /// match those of this version of the server, or if a migration fails. The
+    /// migrations applied before the failure stay recorded.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.applied.name(), operation = "migrate"))]
+    pub async fn migrate(&self, dry_run: bool) -> Result<(), ErrorType> {
+        if self.initialise(dry_run).await? {
+            return Ok(());
+        }
+
+        let pending = self.pending().await?;
+        if pending.is_empty() {
+            info!("The database is up to date");
+            return Ok(());
+        }
+
+        for migration in pending {
+            let (version, name) = (migration.version(), migration.name());
+
+            if dry_run {
+                let count = migration
+                    .pending(self.database, self.collections)
+                    .await
+                    .map_err(|err| migration_error(err, "cannot be planned", version, name))?;
+                info!(version, name, count, "Would apply a migration");
+                continue;
+            }
+
+            let changed = migration
+                .apply(self.database, self.collections)
+                .await
+                .map_err(|err| migration_error(err, "failed", version, name))?;
+
+            let record = AppliedMigration {
+                version,
+                name: name.to_string(),
+                applied_at: Utc::now(),
+                changed,
+            };
+            match self.applied.insert_one(&record).await {
+                Ok(_) => info!(version, name, changed, "Applied a migration"),
+                // Another server applied the same migration at the same time.
+                Err(err) if is_duplicate_key_error(&err) => {
+                    info!(version, name, "The migration was applied concurrently");
+                }
+                Err(err) => return Err(migration_error(err, "cannot be recorded", version, name)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records every migration as applied when no migration is recorded and the
+    /// database holds no data yet, since a new database already has the shape of
+    /// this version of the server. A dry run records nothing. Returns whether the
+    /// database was new.
+    async fn initialise(&self, dry_run: bool) -> Result<bool, ErrorType> {
+        if !self.applied().await?.is_empty() {
+            return Ok(false);
+        }
+
+        let init_error = |err: mongodb::error::Error| {
+            error!(error = %err, "Error initialising the database");
+            ErrorType::ServerStartingError(format!("The database cannot be initialised: {}", err))
+        };
+
+        let collections = self
+            .database
+            .list_collection_names()
+            .await
+            .map_err(init_error)?;
+        for collection in collections {
+            if collection == self.collections.migrations {
+                continue;
+            }
+            let count = self
+                .database
+                .collection::<Document>(&collection)
+                .estimated_document_count()
+                .await
+                .map_err(init_error)?;
+            if count > 0 {
+                return Ok(false);
+            }
+        }
+
+        let Some(latest) = self.migrations.last().map(|migration| migration.version()) else {
+            return Ok(true);
+        };
+        if dry_run {
+            info!(version = latest, "Would initialise a new database");
+            return Ok(true);
+        }
+
+        let records: Vec<AppliedMigration> = self
+            .migrations
+            .iter()
+            .map(|migration| AppliedMigration {
+                version: migration.version(),
+                name: migration.name().to_string(),
+                applied_at: Utc::now(),
+                changed: 0,
+            })
+            .collect();
+        match self.applied.insert_many(&records).await {
+            Ok(_) => info!(version = latest, "Initialised a new database"),
+            // Another server initialised the same database at the same time.
+            Err(err) if is_duplicate_key_error(&err) => {
+                info!(
+                    version = latest,
+                    "The database was initialised concurrently"
+                );
+            }
+            Err(err) => return Err(init_error(err)),
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the migrations not applied to the database yet, after checking that
+    /// the applied ones are the first migrations of this version of the server.
+    async fn pending(&self) -> Result<Vec<&dyn Migration>, ErrorType> {
+        let applied = self.applied().await?;
+
+        for (index, record) in applied.iter().enumerate() {
+            match self.migrations.get(index) {
+                Some(migration)
+                    if migration.version() == record.version && migration.name() == record.name => {
+                }
+                Some(migration) if migration.version() == record.version => {
+                    return Err(ErrorType::ServerStartingError(format!(
+                        "The database has migration {} ({}) where this version of the \
+                         server expects {} ({})",
+                        record.version,
+                        record.name,
+                        migration.version(),
+                        migration.name()
+                    )));
+                }
+                Some(migration) if migration.version() < record.version => {
+                    return Err(ErrorType::ServerStartingError(format!(
+                        "The database has migration {} ({}) but not the earlier migration {} ({})",
+                        record.version,
+                        record.name,
+                        migration.version(),
+                        migration.name()
+                    )));
+                }
+                _ => {
+                    return Err(ErrorType::ServerStartingError(format!(
+                        "The database has migration {} ({}), which this version of the \
+                         server does not know; it was migrated by a newer version",
+                        record.version, record.name
+                    )));
+                }
+            }
+        }
+
+        Ok(self.migrations[applied.len()..]
+            .iter()
+            .map(Box::as_ref)
+            .collect())
+    }
+
+    /// Returns the migrations applied to the database, in the order of their versions.
+    async fn applied(&self) -> Result<Vec<AppliedMigration>, ErrorType> {
+        let read_error = |err: mongodb::error::Error| {
+            error!(error = %err, "Error reading the applied migrations");
+            ErrorType::ServerStartingError(format!(
+                "The applied migrations cannot be read: {}",
+                err
+            ))
+        };
+
+        let mut cursor = self
+            .applied
+            .find(doc! {})
+            .sort(doc! { "_id": 1 })
+            .await
+            .map_err(read_error)?;
+
+        let mut applied = Vec::new();
+        while let Some(record) = cursor.try_next().await.map_err(read_error)? {
+            applied.push(record);
+        }
+        Ok(applied)
+    }
+}
+
+/// Logs the failure of a migration and maps it to `ErrorType::ServerStartingError`.
+fn migration_error(
+    err: mongodb::error::Error,
+    failure: &str,
+    version: u32,
+    name: &str,
+) -> ErrorType {
+    error!(error = %err, version, name, "Error migrating the database");
+    ErrorType::ServerStartingError(format!(
+        "Migration {} ({}) {}: {}",
+        version, name, failure, err
+    ))
+}
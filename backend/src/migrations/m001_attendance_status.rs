@@ -0,0 +1,72 @@
+//! This module defines the first migration, which replaces the boolean `flag` of the
+//! legacy attendance records with a `status`: `absent` when the flag was false, and
+//! `present` otherwise.
+
+use crate::config::CollectionNames;
+use crate::migration::Migration;
+use async_trait::async_trait;
+use mongodb::{
+    Database,
+    bson::{Document, doc},
+};
+
+/// Converts the `flag` of the attendance records, archived or not, to a `status`.
+pub struct AttendanceStatusMigration;
+
+#[async_trait]
+impl Migration for AttendanceStatusMigration {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "attendance_status"
+    }
+
+    async fn pending(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error> {
+        let mut count = 0;
+        for collection in [&collections.records, &collections.archived_records] {
+            count += database
+                .collection::<Document>(collection)
+                .count_documents(legacy_filter())
+                .await?;
+        }
+        Ok(count)
+    }
+
+    async fn apply(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error> {
+        // A record having both fields keeps its status and only loses the flag.
+        let pipeline = vec![
+            doc! { "$set": {
+                "status": { "$ifNull": [
+                    "$status",
+                    { "$cond": [{ "$eq": ["$flag", false] }, "absent", "present"] },
+                ] },
+            } },
+            doc! { "$unset": "flag" },
+        ];
+
+        let mut changed = 0;
+        for collection in [&collections.records, &collections.archived_records] {
+            changed += database
+                .collection::<Document>(collection)
+                .update_many(legacy_filter(), pipeline.clone())
+                .await?
+                .modified_count;
+        }
+        Ok(changed)
+    }
+}
+
+/// Matches the records still having the legacy shape.
+fn legacy_filter() -> Document {
+    doc! { "$or": [{ "status": { "$exists": false } }, { "flag": { "$exists": true } }] }
+}
@@ -0,0 +1,63 @@
+//! This module defines the second migration, which replaces the single `class` of
+//! the teachers with a list of `classes`: the class, or no class when it was unset.
+
+use crate::config::CollectionNames;
+use crate::migration::Migration;
+use async_trait::async_trait;
+use mongodb::{
+    Database,
+    bson::{Document, doc},
+};
+
+/// Converts the `class` of the teachers to `classes`.
+pub struct TeacherClassesMigration;
+
+#[async_trait]
+impl Migration for TeacherClassesMigration {
+    fn version(&self) -> u32 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "teacher_classes"
+    }
+
+    async fn pending(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error> {
+        database
+            .collection::<Document>(&collections.teachers)
+            .count_documents(legacy_filter())
+            .await
+    }
+
+    async fn apply(
+        &self,
+        database: &Database,
+        collections: &CollectionNames,
+    ) -> Result<u64, mongodb::error::Error> {
+        // A teacher having both fields keeps their classes and only loses the class.
+        let pipeline = vec![
+            doc! { "$set": {
+                "classes": { "$ifNull": [
+                    "$classes",
+                    { "$cond": [{ "$eq": [{ "$ifNull": ["$class", null] }, null] }, [], ["$class"]] },
+                ] },
+            } },
+            doc! { "$unset": "class" },
+        ];
+
+        Ok(database
+            .collection::<Document>(&collections.teachers)
+            .update_many(legacy_filter(), pipeline)
+            .await?
+            .modified_count)
+    }
+}
+
+/// Matches the teachers still having the legacy shape.
+fn legacy_filter() -> Document {
+    doc! { "$or": [{ "classes": { "$exists": false } }, { "class": { "$exists": true } }] }
+}
@@ -1,12 +1,60 @@
 //! This module defines the 'Attendance' model.
 
-use chrono::{DateTime, Utc};
-use mongodb::bson::oid::ObjectId;
+use crate::error::ErrorType;
+use crate::models::page_model::SortOrder;
+use chrono::{DateTime, NaiveDate, Utc};
+use mongodb::bson::{Bson, oid::ObjectId};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The status of a student at a class, as set by the teacher.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AttendanceStatus {
+    /// The student attended the class.
+    #[default]
+    Present,
+    /// The student missed the class.
+    Absent,
+    /// The student attended the class but arrived late.
+    Late,
+    /// The student missed the class with a valid excuse.
+    Excused,
+    /// The student missed the class on medical grounds.
+    MedicalLeave,
+    /// The student missed the class while on official duty.
+    OnDuty,
+}
+
+impl AttendanceStatus {
+    /// Tells whether the student attended the class, on time or late.
+    pub fn is_attended(self) -> bool {
+        matches!(self, AttendanceStatus::Present | AttendanceStatus::Late)
+    }
+
+    /// Tells whether the student missed the class with a valid excuse, including
+    /// medical leave and official duty.
+    pub fn is_excused(self) -> bool {
+        matches!(
+            self,
+            AttendanceStatus::Excused | AttendanceStatus::MedicalLeave | AttendanceStatus::OnDuty
+        )
+    }
+}
 
 /// This struct is used to model the data of attendance, including ID, student_id,
-/// class, time, and attendance flag.
+/// class, session, day, time, status, the optional reason and late minutes, and
+/// the teacher who marked it.
+///
+/// A student has at most one record per session, or per class and day for records
+/// without a session. A session with records cannot move to another day.
+///
+/// Older records only store a boolean `flag` instead of a `status`; they are read
+/// as `Present` when the flag is set and `Absent` otherwise. The first migration
+/// (see `migrations::m001_attendance_status`) converts them, but they stay readable
+/// until it is applied.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "AttendanceDocument")]
 pub struct Attendance {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
@@ -14,10 +62,370 @@ pub struct Attendance {
     pub student_id: ObjectId,
     /// The class for which attendance is being marked
     pub class_id: Option<ObjectId>,
+    /// The session of the class for which attendance is being marked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<ObjectId>,
+    /// The calendar day the attendance is for: the day of the session, or the day
+    /// it was marked when there is no session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<NaiveDate>,
     /// The time when attendance was marked
     /// The line below tells the serde how to deserialise the DateTime
     #[serde(with = "chrono::serde::ts_seconds")]
     pub time: DateTime<Utc>,
-    /// The flag indicating attendance status (true for present, false for absent)
-    pub flag: bool,
+    /// The attendance status of the student
+    pub status: AttendanceStatus,
+    /// The reason given for the status, such as the excuse for an absence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// How many minutes late the student was, for the `Late` status
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub late_minutes: Option<u32>,
+    /// The reference to the Teacher who marked the attendance, absent when an
+    /// admin marked it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marked_by: Option<ObjectId>,
+}
+
+impl Attendance {
+    /// Tells whether two records mark the same student for the same session, or
+    /// for the same class and day when there is no session. Legacy records without a
+    /// day never clash.
+    pub fn is_same_mark(&self, other: &Attendance) -> bool {
+        if self.date.is_none() || self.student_id != other.student_id {
+            return false;
+        }
+
+        match self.session_id {
+            Some(_) => self.session_id == other.session_id,
+            None => {
+                other.session_id.is_none()
+                    && self.class_id == other.class_id
+                    && self.date == other.date
+            }
+        }
+    }
+
+    /// Returns the day the record is for: its `date`, or the day it was marked for
+    /// legacy records without one.
+    pub fn day(&self) -> NaiveDate {
+        self.date.unwrap_or(self.time.date_naive())
+    }
+}
+
+/// The stored shape of an attendance record, accepting both the current `status`
+/// field and the legacy `flag` field.
+#[derive(Deserialize)]
+struct AttendanceDocument {
+    #[serde(rename = "_id")]
+    id: Option<ObjectId>,
+    student_id: ObjectId,
+    class_id: Option<ObjectId>,
+    session_id: Option<ObjectId>,
+    date: Option<NaiveDate>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    time: DateTime<Utc>,
+    status: Option<AttendanceStatus>,
+    flag: Option<bool>,
+    reason: Option<String>,
+    late_minutes: Option<u32>,
+    marked_by: Option<ObjectId>,
+}
+
+impl From<AttendanceDocument> for Attendance {
+    fn from(document: AttendanceDocument) -> Self {
+        let status = document.status.unwrap_or(match document.flag {
+            Some(false) => AttendanceStatus::Absent,
+            _ => AttendanceStatus::Present,
+        });
+
+        Self {
+            id: document.id,
+            student_id: document.student_id,
+            class_id: document.class_id,
+            session_id: document.session_id,
+            date: document.date,
+            time: document.time,
+            status,
+            reason: document.reason,
+            late_minutes: document.late_minutes,
+            marked_by: document.marked_by,
+        }
+    }
+}
+
+/// What to do when attendance is marked again for the same student, class and
+/// session (or day).
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// Fail with `409 Conflict`.
+    #[default]
+    Reject,
+    /// Return the existing record unchanged.
+    ReturnExisting,
+}
+
+/// This struct is used to model the request to mark the attendance of a student.
+#[derive(Debug, Deserialize, Clone)]
+pub struct MarkAttendanceRequest {
+    /// The reference to Student
+    pub student_id: ObjectId,
+    /// The class for which attendance is being marked, taken from the session if omitted
+    pub class_id: Option<ObjectId>,
+    /// The session of the class for which attendance is being marked
+    pub session_id: Option<ObjectId>,
+    /// The attendance status of the student, `present` by default
+    #[serde(default)]
+    pub status: AttendanceStatus,
+    /// The reason given for the status
+    pub reason: Option<String>,
+    /// How many minutes late the student was, only kept for the `late` status
+    pub late_minutes: Option<u32>,
+    /// What to do if the student is already marked, `reject` by default
+    #[serde(default)]
+    pub on_duplicate: DuplicatePolicy,
+}
+
+/// The status of one student in a roll call, given either as a bare status or with
+/// the reason and late minutes.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RollCallEntry {
+    /// Only the attendance status of the student.
+    Status(AttendanceStatus),
+    /// The attendance status of the student along with its details.
+    Detailed {
+        /// The attendance status of the student
+        status: AttendanceStatus,
+        /// The reason given for the status
+        reason: Option<String>,
+        /// How many minutes late the student was, only kept for the `late` status
+        late_minutes: Option<u32>,
+    },
+}
+
+impl RollCallEntry {
+    /// Splits the entry into its status, reason and late minutes. The late minutes
+    /// are dropped unless the status is `Late`.
+    pub fn into_parts(self) -> (AttendanceStatus, Option<String>, Option<u32>) {
+        match self {
+            RollCallEntry::Status(status) => (status, None, None),
+            RollCallEntry::Detailed {
+                status,
+                reason,
+                late_minutes,
+            } => (
+                status,
+                reason,
+                late_minutes.filter(|_| status == AttendanceStatus::Late),
+            ),
+        }
+    }
+}
+
+/// This struct is used to model the roll call of a class session: the status of
+/// each student, keyed by student ID.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RollCallRequest {
+    /// The status of each student, keyed by the student's ID
+    pub statuses: BTreeMap<String, RollCallEntry>,
+    /// What to do with students who are already marked, `reject` by default
+    #[serde(default)]
+    pub on_duplicate: DuplicatePolicy,
+}
+
+/// This struct is used to model the outcome of the roll call for one student.
+#[derive(Debug, Serialize, Clone)]
+pub struct RollCallResult {
+    /// The student ID as given in the request
+    pub student_id: String,
+    /// The attendance record, if it was marked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attendance: Option<Attendance>,
+    /// Why the attendance was not marked, if it was not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RollCallResult {
+    /// Creates the result of a student whose attendance was not marked.
+    pub fn rejected(student_id: String, error: &str) -> Self {
+        Self {
+            student_id,
+            attendance: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// This struct is used to model the response to a roll call.
+#[derive(Debug, Serialize, Clone)]
+pub struct RollCallResponse {
+    /// How many students were newly marked
+    pub marked: usize,
+    /// How many students were rejected
+    pub rejected: usize,
+    /// The outcome for each student, in order of student ID
+    pub results: Vec<RollCallResult>,
+}
+
+/// This struct is used to model an optional range of days, given as the `from` and
+/// `to` query parameters. Records are matched on the day of their session, or the
+/// day they were marked.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DateRange {
+    /// The first day included
+    pub from: Option<NaiveDate>,
+    /// The last day included
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// Checks that the range does not end before it starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::InvalidInput` if `to` is before `from`.
+    pub fn validate(&self) -> Result<(), ErrorType> {
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && to < from
+        {
+            return Err(ErrorType::InvalidInput(
+                "The range must not end before it starts".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Tells whether a day falls within the range.
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| from <= date) && self.to.is_none_or(|to| date <= to)
+    }
+
+    /// Tells whether the range has no bounds.
+    pub fn is_unbounded(&self) -> bool {
+        self.from.is_none() && self.to.is_none()
+    }
+}
+
+/// The field an attendance listing is sorted on.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttendanceSortField {
+    /// Sort by the time the attendance was marked.
+    #[default]
+    Time,
+    /// Sort by ID, which is the order of creation.
+    Id,
+}
+
+impl AttendanceSortField {
+    /// Returns the name of the field in the `records` collection.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            AttendanceSortField::Time => "time",
+            AttendanceSortField::Id => "_id",
+        }
+    }
+
+    /// Returns the value of the field for the given record, as it is stored.
+    pub fn value_of(self, attendance: &Attendance) -> Bson {
+        match self {
+            AttendanceSortField::Time => Bson::Int64(attendance.time.timestamp()),
+            AttendanceSortField::Id => attendance.id.map(Bson::ObjectId).unwrap_or(Bson::Null),
+        }
+    }
+}
+
+/// This struct is used to model the query parameters of a listing of attendance
+/// records.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct AttendanceListQuery {
+    /// The first day to include
+    pub from: Option<NaiveDate>,
+    /// The last day to include
+    pub to: Option<NaiveDate>,
+    /// Only include records with this status
+    pub status: Option<AttendanceStatus>,
+    /// Only include records of this student
+    pub student_id: Option<ObjectId>,
+    /// Only include records of this class
+    pub class_id: Option<ObjectId>,
+    /// The field to sort on, `time` by default
+    #[serde(default)]
+    pub sort: AttendanceSortField,
+    /// The sort direction, `asc` by default
+    #[serde(default)]
+    pub order: SortOrder,
+    /// The number of records per page
+    pub limit: Option<u32>,
+    /// The cursor returned with the previous page
+    pub cursor: Option<String>,
+    /// Only include records of these classes; set by the server from the classes
+    /// assigned to the caller
+    #[serde(skip)]
+    pub class_scope: Option<Vec<ObjectId>>,
+}
+
+impl AttendanceListQuery {
+    /// Returns the range of days of the listing.
+    pub fn range(&self) -> DateRange {
+        DateRange {
+            from: self.from,
+            to: self.to,
+        }
+    }
+
+    /// Tells whether a record matches the filters of the listing, cursor aside.
+    pub fn matches(&self, attendance: &Attendance) -> bool {
+        self.student_id
+            .is_none_or(|student_id| attendance.student_id == student_id)
+            && self
+                .class_id
+                .is_none_or(|class_id| attendance.class_id == Some(class_id))
+            && self.class_scope.as_ref().is_none_or(|classes| {
+                attendance
+                    .class_id
+                    .is_some_and(|class_id| classes.contains(&class_id))
+            })
+            && self.status.is_none_or(|status| attendance.status == status)
+            && self.range().contains(attendance.day())
+    }
+}
+
+/// This struct is used to model the attendance of a student in one class.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClassAttendanceSummary {
+    /// The class the figures are for
+    pub class_id: Option<ObjectId>,
+    /// How many sessions the class held while the student was enrolled, counting
+    /// each day the student was marked without a session as one more
+    pub sessions_held: u64,
+    /// How many sessions, or days without a session, the student was marked for
+    pub marked: u64,
+    /// How many sessions the student attended, on time or late
+    pub attended: u64,
+    /// How many sessions the student arrived late to
+    pub late: u64,
+    /// How many sessions the student missed with a valid excuse
+    pub excused: u64,
+    /// The share of sessions attended, between 0 and 100
+    pub percentage: f64,
+}
+
+/// This struct is used to model the attendance summary of a student.
+#[derive(Debug, Serialize, Clone)]
+pub struct StudentAttendanceSummary {
+    /// The reference to Student
+    pub student_id: ObjectId,
+    /// The first day included in the summary, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    /// The last day included in the summary, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    /// The attendance of the student in each class, in order of class ID
+    pub classes: Vec<ClassAttendanceSummary>,
 }
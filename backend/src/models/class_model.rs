@@ -1,13 +1,120 @@
 //! This module defines the `Class` model.
 
+use crate::error::ErrorType;
+use chrono::{NaiveTime, Weekday};
 use mongodb::bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-/// This struct is used to model the data of a class, including its ID and name.
+/// This struct is used to model the data of a class, including its ID, name, course
+/// metadata, default weekly schedule, and whether it is archived.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Class {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<ObjectId>,
     /// The name of the class/subject
     pub name: String,
+    /// The code of the course, such as `CS101`
+    pub course_code: Option<String>,
+    /// The department offering the class
+    pub department: Option<String>,
+    /// The term the class is taught in, such as `2026-fall`
+    pub term: Option<String>,
+    /// The credits earned by taking the class
+    pub credits: Option<i32>,
+    /// The largest number of students the class is meant for
+    pub capacity: Option<i32>,
+    /// The weekly slots the class usually meets in
+    #[serde(default)]
+    pub schedule: Vec<ScheduleSlot>,
+    /// Whether the class is over and only kept for its history
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl Class {
+    /// Checks that the class is not archived, as an archived class takes no new
+    /// enrollments, sessions or teachers.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Archived` if the class is archived.
+    pub fn require_active(&self) -> Result<(), ErrorType> {
+        if self.archived {
+            return Err(ErrorType::Archived("The class is archived".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// This struct is used to model a weekly slot of the default schedule of a class.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ScheduleSlot {
+    /// The day of the week, such as `Mon`
+    pub weekday: Weekday,
+    /// The time when the class starts, such as `09:00:00`
+    pub start_time: NaiveTime,
+    /// The time when the class ends
+    pub end_time: NaiveTime,
+    /// The room where the class usually takes place
+    pub room: Option<String>,
+}
+
+/// This struct is used to model a partial update of a class, such as a rename.
+/// Only the fields that are present are changed, and the optional metadata is
+/// cleared when it is given as `null`; the class is archived and unarchived through
+/// their own routes.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct ClassUpdate {
+    /// The new name of the class
+    pub name: Option<String>,
+    /// The new code of the course, or `null` to clear it
+    #[serde(default, deserialize_with = "nullable")]
+    pub course_code: Option<Option<String>>,
+    /// The new department, or `null` to clear it
+    #[serde(default, deserialize_with = "nullable")]
+    pub department: Option<Option<String>>,
+    /// The new term, or `null` to clear it
+    #[serde(default, deserialize_with = "nullable")]
+    pub term: Option<Option<String>>,
+    /// The new credits, or `null` to clear them
+    #[serde(default, deserialize_with = "nullable")]
+    pub credits: Option<Option<i32>>,
+    /// The new capacity, or `null` to clear it
+    #[serde(default, deserialize_with = "nullable")]
+    pub capacity: Option<Option<i32>>,
+    /// The new default schedule, replacing the whole previous one
+    pub schedule: Option<Vec<ScheduleSlot>>,
+}
+
+/// Reads a field that may be absent, `null` or set: an absent field is left to
+/// `#[serde(default)]` as `None`, while `null` becomes `Some(None)`.
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// This struct is used to model the query parameters of a listing of classes.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct ClassListQuery {
+    /// A case-insensitive substring to look for in the name or the course code
+    pub search: Option<String>,
+    /// Only the classes of this course code
+    pub course_code: Option<String>,
+    /// Only the classes of this department
+    pub department: Option<String>,
+    /// Only the classes of this term
+    pub term: Option<String>,
+    /// Only the classes earning this many credits
+    pub credits: Option<i32>,
+    /// Only the classes meant for at least this many students
+    pub min_capacity: Option<i32>,
+    /// Only the classes meeting on this day of the week
+    pub weekday: Option<Weekday>,
+    /// Whether to list the archived classes instead of the active ones, `false` by
+    /// default
+    #[serde(default)]
+    pub archived: bool,
 }
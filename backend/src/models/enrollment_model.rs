@@ -0,0 +1,62 @@
+//! This module defines the `Enrollment` model.
+
+use chrono::NaiveDate;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// This struct is used to model the enrollment of a student in a class, including
+/// its ID, the student, the class, and the dates between which it is effective.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Enrollment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The reference to Student
+    pub student_id: ObjectId,
+    /// The reference to Class
+    pub class_id: ObjectId,
+    /// The first day on which the student attends the class
+    pub start_date: NaiveDate,
+    /// The last day on which the student attends the class, if the enrollment has ended
+    pub end_date: Option<NaiveDate>,
+}
+
+impl Enrollment {
+    /// Tells whether the enrollment is effective on the given date.
+    pub fn is_active_on(&self, date: NaiveDate) -> bool {
+        self.start_date <= date && self.end_date.is_none_or(|end_date| date <= end_date)
+    }
+
+    /// Tells whether the enrollment is effective on any day from `start_date` to
+    /// `end_date`, the latter being open-ended if `None`.
+    pub fn overlaps(&self, start_date: NaiveDate, end_date: Option<NaiveDate>) -> bool {
+        self.end_date.is_none_or(|own_end| start_date <= own_end)
+            && end_date.is_none_or(|end_date| self.start_date <= end_date)
+    }
+}
+
+/// This struct is used to model the request to enroll a student in a class.
+#[derive(Clone, Deserialize, Debug)]
+pub struct EnrollRequest {
+    /// The student to enroll
+    pub student_id: ObjectId,
+    /// The first day of the enrollment, today by default
+    pub start_date: Option<NaiveDate>,
+    /// The last day of the enrollment, open-ended by default
+    pub end_date: Option<NaiveDate>,
+}
+
+/// This struct is used to model the request to unenroll a student from a class.
+#[derive(Clone, Deserialize, Debug)]
+pub struct UnenrollRequest {
+    /// The student to unenroll
+    pub student_id: ObjectId,
+    /// The last day on which the student attends the class, today by default
+    pub end_date: Option<NaiveDate>,
+}
+
+/// This struct is used to model the query parameters of a class roster.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct RosterQuery {
+    /// The day for which the roster is listed, today by default
+    pub date: Option<NaiveDate>,
+}
@@ -0,0 +1,54 @@
+//! This module defines the models of the health probes.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Whether a dependency answered its health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    /// The dependency answered in time
+    Up,
+    /// The dependency failed or did not answer in time
+    Down,
+}
+
+/// The result of the health check of a single dependency.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyHealth {
+    /// Whether the dependency is up
+    pub status: DependencyStatus,
+    /// How long the check took, in milliseconds
+    pub latency_ms: u64,
+    /// Why the dependency is down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the server can serve requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerStatus {
+    /// The process is running
+    Alive,
+    /// Every dependency is up
+    Ready,
+    /// At least one dependency is down
+    NotReady,
+}
+
+/// The response of the liveness probe.
+#[derive(Debug, Clone, Serialize)]
+pub struct LivenessResponse {
+    /// Always `alive`
+    pub status: ServerStatus,
+}
+
+/// The response of the readiness probe.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessResponse {
+    /// `ready` when every dependency is up, `not_ready` otherwise
+    pub status: ServerStatus,
+    /// The result of each dependency's check, by name
+    pub dependencies: BTreeMap<String, DependencyHealth>,
+}
@@ -0,0 +1,229 @@
+//! This module defines the `Page` model used by the paginated listings, along with
+//! the opaque cursor that points to the next page.
+
+use crate::error::ErrorType;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use mongodb::{
+    Collection,
+    bson::{self, Bson, Document, doc, oid::ObjectId},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::cmp::Ordering;
+use tokio_stream::StreamExt;
+use tracing::error;
+
+/// The number of items returned when the client does not ask for a limit.
+pub const DEFAULT_PAGE_LIMIT: u32 = 20;
+
+/// The largest number of items a client may ask for in a single page.
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+/// This struct is used to model a page of a listing, including its items and the
+/// cursor of the next page.
+#[derive(Clone, Serialize, Debug)]
+pub struct Page<T> {
+    /// The items of the page
+    pub items: Vec<T>,
+    /// The cursor to pass to get the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// The direction in which a listing is sorted.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Smallest values first.
+    #[default]
+    Asc,
+    /// Largest values first.
+    Desc,
+}
+
+impl SortOrder {
+    /// Returns the MongoDB sort direction of the order.
+    pub fn direction(self) -> i32 {
+        match self {
+            SortOrder::Asc => 1,
+            SortOrder::Desc => -1,
+        }
+    }
+
+    /// Applies the order to an ascending comparison.
+    pub fn apply(self, ordering: Ordering) -> Ordering {
+        match self {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+}
+
+/// The position of the last item of a page: the value of the sort field and the
+/// ID of the item, which breaks ties between equal values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    /// The value of the sort field of the last item
+    pub value: Bson,
+    /// The ID of the last item
+    pub id: ObjectId,
+}
+
+impl Cursor {
+    /// Encodes the cursor into an opaque, URL-safe string.
+    pub fn encode(&self) -> String {
+        let document = doc! { "v": self.value.clone(), "id": self.id };
+        let bytes = bson::to_vec(&document).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// Decodes a cursor previously returned by `encode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::InvalidInput` if the string is not a valid cursor.
+    pub fn decode(encoded: &str) -> Result<Self, ErrorType> {
+        let invalid = || ErrorType::InvalidInput("The cursor is not valid".to_string());
+
+        let bytes = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| invalid())?;
+        let document: Document = bson::from_slice(&bytes).map_err(|_| invalid())?;
+        let value = document.get("v").cloned().ok_or_else(invalid)?;
+        let id = document.get_object_id("id").map_err(|_| invalid())?;
+
+        Ok(Self { value, id })
+    }
+
+    /// Builds the MongoDB filter matching the items that come after the cursor when
+    /// sorting on `field` (then on `_id`) in the given order.
+    pub fn filter(&self, field: &str, order: SortOrder) -> Document {
+        let operator = match order {
+            SortOrder::Asc => "$gt",
+            SortOrder::Desc => "$lt",
+        };
+
+        if field == "_id" {
+            return doc! { "_id": { operator: self.id } };
+        }
+
+        doc! {
+            "$or": [
+                { field: { operator: self.value.clone() } },
+                { field: self.value.clone(), "_id": { operator: self.id } },
+            ]
+        }
+    }
+
+    /// Tells whether an item with the given sort value and ID comes after the cursor
+    /// in the given order.
+    pub fn is_followed_by(&self, value: &Bson, id: ObjectId, order: SortOrder) -> bool {
+        let ordering = compare_bson(value, &self.value).then_with(|| id.cmp(&self.id));
+        order.apply(ordering) == Ordering::Greater
+    }
+}
+
+/// Clamps the requested page size between 1 and `MAX_PAGE_LIMIT`.
+pub fn page_limit(limit: Option<u32>) -> u32 {
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+}
+
+/// Compares two BSON values of the same type. Values of different or unsupported
+/// types compare as equal.
+pub fn compare_bson(a: &Bson, b: &Bson) -> Ordering {
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => a.cmp(b),
+        (Bson::Int32(a), Bson::Int32(b)) => a.cmp(b),
+        (Bson::Int64(a), Bson::Int64(b)) => a.cmp(b),
+        (Bson::Int32(a), Bson::Int64(b)) => i64::from(*a).cmp(b),
+        (Bson::Int64(a), Bson::Int32(b)) => a.cmp(&i64::from(*b)),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Escapes the regular-expression metacharacters of a string, so that it can be
+/// used to match a literal substring with `$regex`.
+pub fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Splits off the extra item fetched past the limit of a listing, and builds the
+/// next cursor from the last item kept if there are more items to come.
+pub fn into_page<T, F>(mut items: Vec<T>, limit: u32, cursor_of: F) -> Page<T>
+where
+    F: Fn(&T) -> Cursor,
+{
+    let has_more = items.len() > limit as usize;
+    items.truncate(limit as usize);
+
+    let next_cursor = if has_more {
+        items.last().map(|item| cursor_of(item).encode())
+    } else {
+        None
+    };
+
+    Page { items, next_cursor }
+}
+
+/// Fetches a page of a MongoDB listing: the documents matching every condition and
+/// coming after the cursor, sorted on `field` (then on `_id`) in the given order.
+/// One document past the limit is fetched to tell whether more are to come, and
+/// `cursor_of` gives the position of the last document kept.
+///
+/// # Errors
+///
+/// Returns `ErrorType::InvalidInput` if the cursor is not valid, or
+/// `ErrorType::ServerError` if the documents cannot be fetched.
+pub async fn find_page<T, F>(
+    collection: &Collection<T>,
+    mut conditions: Vec<Document>,
+    field: &str,
+    order: SortOrder,
+    cursor: Option<&str>,
+    limit: Option<u32>,
+    cursor_of: F,
+) -> Result<Page<T>, ErrorType>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+    F: Fn(&T) -> Cursor,
+{
+    let limit = page_limit(limit);
+    if let Some(cursor) = cursor {
+        conditions.push(Cursor::decode(cursor)?.filter(field, order));
+    }
+
+    let filter = if conditions.is_empty() {
+        doc! {}
+    } else {
+        doc! { "$and": conditions }
+    };
+    let direction = order.direction();
+    let sort = if field == "_id" {
+        doc! { "_id": direction }
+    } else {
+        doc! { field: direction, "_id": direction }
+    };
+
+    let fetch_error = |err: mongodb::error::Error| {
+        error!(error = %err, collection = %collection.name(), "Error fetching a page");
+        ErrorType::ServerError("Server Error".to_string())
+    };
+    let mut documents = collection
+        .find(filter)
+        .sort(sort)
+        .limit(i64::from(limit) + 1)
+        .await
+        .map_err(fetch_error)?;
+
+    let mut items = Vec::new();
+    while let Some(item) = documents.try_next().await.map_err(fetch_error)? {
+        items.push(item);
+    }
+
+    Ok(into_page(items, limit, cursor_of))
+}
@@ -0,0 +1,56 @@
+//! This module defines the `Session` model.
+
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// This struct is used to model a session (lecture) of a class, including its ID,
+/// the class, its start and end times, the room, and the teacher taking it.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Session {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The reference to Class
+    pub class_id: ObjectId,
+    /// The time when the session starts
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    /// The time when the session ends
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    /// The room where the session takes place
+    pub room: Option<String>,
+    /// The reference to the Teacher taking the session
+    pub teacher_id: Option<ObjectId>,
+}
+
+/// This struct is used to model the request to create a session of a class.
+#[derive(Clone, Deserialize, Debug)]
+pub struct NewSession {
+    /// The time when the session starts
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub start_time: DateTime<Utc>,
+    /// The time when the session ends
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub end_time: DateTime<Utc>,
+    /// The room where the session takes place
+    pub room: Option<String>,
+    /// The reference to the Teacher taking the session
+    pub teacher_id: Option<ObjectId>,
+}
+
+/// This struct is used to model a partial update of a session. Only the fields
+/// that are present are changed.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct SessionUpdate {
+    /// The new start time of the session
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub start_time: Option<DateTime<Utc>>,
+    /// The new end time of the session
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub end_time: Option<DateTime<Utc>>,
+    /// The new room of the session
+    pub room: Option<String>,
+    /// The new teacher of the session
+    pub teacher_id: Option<ObjectId>,
+}
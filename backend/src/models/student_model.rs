@@ -1,6 +1,7 @@
 //! This module defines the `Student` model.
 
-use mongodb::bson::oid::ObjectId;
+use crate::models::page_model::SortOrder;
+use mongodb::bson::{Bson, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 /// This struct is used to model the data of a student, including their ID, name,
@@ -19,3 +20,94 @@ pub struct Student {
     /// The roll number of the student
     pub roll_number: i32,
 }
+
+/// This struct is used to model a partial update of a student. Only the fields
+/// that are present are changed.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct StudentUpdate {
+    /// The new name of the student
+    pub name: Option<String>,
+    /// The new email of the student
+    pub email: Option<String>,
+    /// The new roll number of the student
+    pub roll_number: Option<i32>,
+}
+
+/// The policy applied to a student's attendance records when the student is deleted.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordsPolicy {
+    /// Refuse to delete a student who still has attendance records.
+    #[default]
+    Block,
+    /// Delete the student's attendance records along with the student.
+    Cascade,
+    /// Move the student's attendance records to the archive before deleting the student.
+    Archive,
+}
+
+/// This struct is used to model the query parameters of a student deletion.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct DeleteStudentQuery {
+    /// What to do with the student's attendance records, `block` by default
+    #[serde(default)]
+    pub records: RecordsPolicy,
+}
+
+/// The field on which a listing of students is sorted.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StudentSortField {
+    /// Sort by ID, which is the order of creation.
+    #[default]
+    Id,
+    /// Sort by name.
+    Name,
+    /// Sort by email.
+    Email,
+    /// Sort by roll number.
+    RollNumber,
+}
+
+impl StudentSortField {
+    /// Returns the name of the field in the `students` collection.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            StudentSortField::Id => "_id",
+            StudentSortField::Name => "name",
+            StudentSortField::Email => "email",
+            StudentSortField::RollNumber => "roll_number",
+        }
+    }
+
+    /// Returns the value of the field for the given student.
+    pub fn value_of(self, student: &Student) -> Bson {
+        match self {
+            StudentSortField::Id => student.id.map(Bson::ObjectId).unwrap_or(Bson::Null),
+            StudentSortField::Name => Bson::String(student.name.clone()),
+            StudentSortField::Email => Bson::String(student.email.clone()),
+            StudentSortField::RollNumber => Bson::Int32(student.roll_number),
+        }
+    }
+}
+
+/// This struct is used to model the query parameters of a listing of students.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct StudentListQuery {
+    /// A case-insensitive substring to look for in the name or the email
+    pub search: Option<String>,
+    /// The smallest roll number to include
+    pub roll_min: Option<i32>,
+    /// The largest roll number to include
+    pub roll_max: Option<i32>,
+    /// The field to sort on, `id` by default
+    #[serde(default)]
+    pub sort: StudentSortField,
+    /// The sort direction, `asc` by default
+    #[serde(default)]
+    pub order: SortOrder,
+    /// The number of students per page
+    pub limit: Option<u32>,
+    /// The cursor returned with the previous page
+    pub cursor: Option<String>,
+}
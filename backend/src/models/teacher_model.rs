@@ -1,10 +1,11 @@
 //! This module defines the `Teacher` model.
 
-use mongodb::bson::oid::ObjectId;
+use crate::models::page_model::SortOrder;
+use mongodb::bson::{Bson, oid::ObjectId};
 use serde::{Deserialize, Serialize};
 
 /// This struct is used to model the data of a teacher, including their ID, name,
-/// email, and assigned class.
+/// email, and assigned classes.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Teacher {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -13,6 +14,70 @@ pub struct Teacher {
     pub name: String,
     /// The email of the teacher
     pub email: String,
-    /// The class taken by the teacher
-    pub class: Option<ObjectId>,
+    /// The classes taken by the teacher
+    #[serde(default)]
+    pub classes: Vec<ObjectId>,
+}
+
+/// This struct is used to model a partial update of a teacher. Only the fields
+/// that are present are changed; the classes are changed through the assignment
+/// routes.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct TeacherUpdate {
+    /// The new name of the teacher
+    pub name: Option<String>,
+    /// The new email of the teacher
+    pub email: Option<String>,
+}
+
+/// The field on which a listing of teachers is sorted.
+#[derive(Clone, Copy, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TeacherSortField {
+    /// Sort by ID, which is the order of creation.
+    #[default]
+    Id,
+    /// Sort by name.
+    Name,
+    /// Sort by email.
+    Email,
+}
+
+impl TeacherSortField {
+    /// Returns the name of the field in the `teachers` collection.
+    pub fn field_name(self) -> &'static str {
+        match self {
+            TeacherSortField::Id => "_id",
+            TeacherSortField::Name => "name",
+            TeacherSortField::Email => "email",
+        }
+    }
+
+    /// Returns the value of the field for the given teacher.
+    pub fn value_of(self, teacher: &Teacher) -> Bson {
+        match self {
+            TeacherSortField::Id => teacher.id.map(Bson::ObjectId).unwrap_or(Bson::Null),
+            TeacherSortField::Name => Bson::String(teacher.name.clone()),
+            TeacherSortField::Email => Bson::String(teacher.email.clone()),
+        }
+    }
+}
+
+/// This struct is used to model the query parameters of a listing of teachers.
+#[derive(Clone, Deserialize, Debug, Default)]
+pub struct TeacherListQuery {
+    /// A case-insensitive substring to look for in the name or the email
+    pub search: Option<String>,
+    /// Only the teachers assigned to this class
+    pub class_id: Option<ObjectId>,
+    /// The field to sort on, `id` by default
+    #[serde(default)]
+    pub sort: TeacherSortField,
+    /// The sort direction, `asc` by default
+    #[serde(default)]
+    pub order: SortOrder,
+    /// The number of teachers per page
+    pub limit: Option<u32>,
+    /// The cursor returned with the previous page
+    pub cursor: Option<String>,
 }
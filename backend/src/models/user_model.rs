@@ -0,0 +1,182 @@
+//! This module defines the `User` model, which holds the credentials used to log in.
+
+use chrono::{DateTime, Utc};
+use mongodb::bson::{
+    oid::ObjectId,
+    serde_helpers::{chrono_datetime_as_bson_datetime, chrono_datetime_as_bson_datetime_optional},
+};
+use serde::{Deserialize, Serialize};
+
+/// The role of a user, which decides the routes they may call.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Manages students, teachers, classes and accounts.
+    Admin,
+    /// Takes sessions and marks attendance.
+    Teacher,
+    /// Reads their own attendance.
+    Student,
+}
+
+/// This struct is used to model the account of a user, including their ID, email,
+/// password hash, role, and the student or teacher they act as.
+///
+/// The password is never stored in clear text; only its Argon2 hash is kept. The
+/// tokens issued before the password was last changed are no longer accepted.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct User {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The email the user logs in with
+    pub email: String,
+    /// The Argon2 hash of the password, in PHC string format
+    pub password_hash: String,
+    /// The role of the user
+    pub role: Role,
+    /// The reference to the Student the user acts as, for the `student` role
+    pub student_id: Option<ObjectId>,
+    /// The reference to the Teacher the user acts as, for the `teacher` role
+    pub teacher_id: Option<ObjectId>,
+    /// The time the password was last changed, if it was
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub password_changed_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    /// Replaces the password hash, and records the change so that the tokens issued
+    /// until now are no longer accepted.
+    pub fn set_password_hash(&mut self, password_hash: String) {
+        self.password_hash = password_hash;
+        self.password_changed_at = Some(Utc::now());
+    }
+
+    /// Tells whether a token issued at the given time, in seconds since the epoch,
+    /// is still accepted: it must not predate the last password change. Tokens only
+    /// carry whole seconds, so a token issued in the second of the change is kept.
+    pub fn accepts_token_issued_at(&self, issued_at: i64) -> bool {
+        self.password_changed_at
+            .is_none_or(|changed_at| issued_at >= changed_at.timestamp())
+    }
+}
+
+/// This struct is used to model the account of a user as returned by the API,
+/// without the password hash.
+#[derive(Clone, Serialize, Debug)]
+pub struct UserResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The email the user logs in with
+    pub email: String,
+    /// The role of the user
+    pub role: Role,
+    /// The reference to the Student the user acts as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub student_id: Option<ObjectId>,
+    /// The reference to the Teacher the user acts as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teacher_id: Option<ObjectId>,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            role: user.role,
+            student_id: user.student_id,
+            teacher_id: user.teacher_id,
+        }
+    }
+}
+
+/// This struct is used to model the request to create an account.
+#[derive(Clone, Deserialize, Debug)]
+pub struct NewUser {
+    /// The email the user logs in with
+    pub email: String,
+    /// The password of the user, in clear text
+    pub password: String,
+    /// The role of the user
+    pub role: Role,
+    /// The reference to the Student the user acts as, required for the `student` role
+    pub student_id: Option<ObjectId>,
+    /// The reference to the Teacher the user acts as, required for the `teacher` role
+    pub teacher_id: Option<ObjectId>,
+}
+
+/// This struct is used to model the request to log in.
+#[derive(Clone, Deserialize, Debug)]
+pub struct LoginRequest {
+    /// The email of the user
+    pub email: String,
+    /// The password of the user
+    pub password: String,
+}
+
+/// This struct is used to model the response to a successful login.
+#[derive(Clone, Serialize, Debug)]
+pub struct LoginResponse {
+    /// The signed JWT to send in the `Authorization: Bearer` header
+    pub token: String,
+    /// The type of the token, always `Bearer`
+    pub token_type: &'static str,
+    /// How many seconds the token is valid for
+    pub expires_in: i64,
+    /// The role of the user
+    pub role: Role,
+}
+
+/// This struct is used to model the request of a logged-in user to change their
+/// password.
+#[derive(Clone, Deserialize, Debug)]
+pub struct PasswordChangeRequest {
+    /// The current password of the user
+    pub current_password: String,
+    /// The new password of the user
+    pub new_password: String,
+}
+
+/// This struct is used to model the request to email a password-reset token.
+#[derive(Clone, Deserialize, Debug)]
+pub struct PasswordResetRequest {
+    /// The email of the account to reset
+    pub email: String,
+}
+
+/// This struct is used to model the request to set a new password with a reset
+/// token.
+#[derive(Clone, Deserialize, Debug)]
+pub struct PasswordResetConfirm {
+    /// The token received by email
+    pub token: String,
+    /// The new password of the user
+    pub new_password: String,
+}
+
+/// This struct is used to model a password-reset token issued to a user. Only the
+/// SHA-256 hash of the token is stored, and the token can be used once before it
+/// expires.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PasswordReset {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    /// The reference to the User the token resets
+    pub user_id: ObjectId,
+    /// The hex-encoded SHA-256 hash of the token
+    pub token_hash: String,
+    /// The time after which the token can no longer be used, stored as a BSON date
+    /// so that a TTL index can delete the expired tokens
+    #[serde(with = "chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+    /// The time when the token was used, if it was
+    #[serde(default, with = "chrono_datetime_as_bson_datetime_optional")]
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl PasswordReset {
+    /// Tells whether the token can still be used at the given time.
+    pub fn is_usable_at(&self, now: DateTime<Utc>) -> bool {
+        self.used_at.is_none() && now < self.expires_at
+    }
+}
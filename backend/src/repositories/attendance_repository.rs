@@ -0,0 +1,732 @@
+//! This module defines the `AttendanceRepository` trait and its implementations.
+
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::models::attendance_model::{
+    Attendance, AttendanceListQuery, AttendanceStatus, ClassAttendanceSummary, DateRange,
+};
+use crate::models::page_model::{Cursor, Page, compare_bson, find_page, into_page, page_limit};
+use crate::transaction::{collect, in_transaction};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveTime};
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Bson, Document, doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tokio::sync::RwLock;
+use tokio_stream::StreamExt;
+use tracing::{error, instrument};
+
+/// Abstracts the storage of attendance records, so that the routes do not
+/// depend on a particular database.
+#[async_trait]
+pub trait AttendanceRepository: Send + Sync {
+    /// Inserts a new attendance record and returns it with its generated ID. Fails
+    /// with `ErrorType::AlreadyExists` if the student is already marked for the
+    /// same session, or for the same class and day when there is no session.
+    async fn insert(&self, attendance: Attendance) -> Result<Attendance, ErrorType>;
+
+    /// Inserts several attendance records in a single write and returns them with
+    /// their generated IDs. Fails with `ErrorType::AlreadyExists` if any of the
+    /// students is already marked.
+    async fn insert_many(&self, attendances: Vec<Attendance>)
+    -> Result<Vec<Attendance>, ErrorType>;
+
+    /// Finds the stored record marking the same student for the same session as the
+    /// given record, or for the same class and day when it has no session.
+    async fn find_duplicate(
+        &self,
+        attendance: &Attendance,
+    ) -> Result<Option<Attendance>, ErrorType>;
+
+    /// Returns all the attendance records of a class session.
+    async fn find_by_session(&self, session_id: ObjectId) -> Result<Vec<Attendance>, ErrorType>;
+
+    /// Returns a page of the attendance records matching the query, sorted and
+    /// starting after the cursor of the query.
+    async fn list(&self, query: &AttendanceListQuery) -> Result<Page<Attendance>, ErrorType>;
+
+    /// Summarizes the attendance of a student in each class over the optional date
+    /// range, in order of class ID. `sessions_held` gives the number of sessions
+    /// each class held over the range while the student was enrolled; a class
+    /// listed there is summarized even if the student has no record in it. Records
+    /// without a day are matched on the day they were marked.
+    async fn summarize_student(
+        &self,
+        student_id: ObjectId,
+        range: &DateRange,
+        sessions_held: &BTreeMap<ObjectId, u64>,
+    ) -> Result<Vec<ClassAttendanceSummary>, ErrorType>;
+
+    /// Counts the attendance records of a student.
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Counts the attendance records of a class session.
+    async fn count_by_session(&self, session_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Counts the attendance records of a class.
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Deletes all the attendance records of a student and returns how many were removed.
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Moves all the attendance records of a student to the archive and returns
+    /// how many were moved.
+    async fn archive_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType>;
+}
+
+/// An `AttendanceRepository` backed by the MongoDB `records` collection. Archived
+/// records are kept in the `archived_records` collection.
+pub struct MongoAttendanceRepository {
+    collection: Collection<Attendance>,
+    archive: Collection<Attendance>,
+}
+
+impl MongoAttendanceRepository {
+    /// Creates a new repository on the given records and archive collections of the
+    /// given database, `records` and `archived_records` by default.
+    pub fn new(database: &Database, collection: &str, archive: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+            archive: database.collection(archive),
+        }
+    }
+
+    /// Creates the unique indexes that allow a single record per student and session,
+    /// and per student, class and day for the records without a session, and the
+    /// indexes of the lookups by student and by class. The session index does not
+    /// depend on the day, so that moving a session keeps its records unique. Legacy
+    /// records without a day are left out of the unique indexes. The `unique_mark`
+    /// index of earlier versions, which also held the records of a session, is
+    /// dropped.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let unique_session_mark = IndexModel::builder()
+            .keys(doc! { "student_id": 1, "session_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_session_mark".to_string())
+                    .unique(true)
+                    .partial_filter_expression(doc! {
+                        "session_id": { "$exists": true },
+                        "date": { "$exists": true },
+                    })
+                    .build(),
+            )
+            .build();
+        let unique_day_mark = IndexModel::builder()
+            .keys(doc! { "student_id": 1, "class_id": 1, "date": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_day_mark".to_string())
+                    .unique(true)
+                    // A null equality also matches the records with no `session_id`
+                    .partial_filter_expression(doc! {
+                        "session_id": Bson::Null,
+                        "date": { "$exists": true },
+                    })
+                    .build(),
+            )
+            .build();
+        let by_student = IndexModel::builder()
+            .keys(doc! { "student_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("student_id".to_string())
+                    .build(),
+            )
+            .build();
+        let by_class = IndexModel::builder()
+            .keys(doc! { "class_id": 1 })
+            .options(IndexOptions::builder().name("class_id".to_string()).build())
+            .build();
+
+        let index_error = |err: mongodb::error::Error| {
+            error!(error = %err, "Error creating the attendance indexes");
+            ErrorType::ServerError("Server Error".to_string())
+        };
+        self.collection
+            .create_indexes([unique_session_mark, unique_day_mark, by_student, by_class])
+            .await
+            .map_err(index_error)?;
+
+        let names = self
+            .collection
+            .list_index_names()
+            .await
+            .map_err(index_error)?;
+        if names.iter().any(|name| name == "unique_mark") {
+            self.collection
+                .drop_index("unique_mark")
+                .await
+                .map_err(index_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Collects every attendance record matching the filter.
+    async fn find_many(&self, filter: Document) -> Result<Vec<Attendance>, ErrorType> {
+        collect(self.collection.find(filter)).await.map_err(|err| {
+            error!(error = %err, "Error fetching the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+}
+
+#[async_trait]
+impl AttendanceRepository for MongoAttendanceRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut attendance: Attendance) -> Result<Attendance, ErrorType> {
+        attendance.id = None;
+
+        let insert_result =
+            in_transaction!(self.collection.insert_one(&attendance)).map_err(map_insert_error)?;
+
+        attendance.id = insert_result.inserted_id.as_object_id();
+        Ok(attendance)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert_many"))]
+    async fn insert_many(
+        &self,
+        mut attendances: Vec<Attendance>,
+    ) -> Result<Vec<Attendance>, ErrorType> {
+        if attendances.is_empty() {
+            return Ok(attendances);
+        }
+
+        for attendance in &mut attendances {
+            attendance.id = None;
+        }
+
+        let insert_result =
+            in_transaction!(self.collection.insert_many(&attendances)).map_err(map_insert_error)?;
+
+        for (index, id) in insert_result.inserted_ids {
+            if let Some(attendance) = attendances.get_mut(index) {
+                attendance.id = id.as_object_id();
+            }
+        }
+
+        Ok(attendances)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_duplicate"))]
+    async fn find_duplicate(
+        &self,
+        attendance: &Attendance,
+    ) -> Result<Option<Attendance>, ErrorType> {
+        let Some(date) = attendance.date else {
+            return Ok(None);
+        };
+
+        let filter = match attendance.session_id {
+            Some(session_id) => doc! {
+                "student_id": attendance.student_id,
+                "session_id": session_id,
+                "date": { "$exists": true },
+            },
+            None => doc! {
+                "student_id": attendance.student_id,
+                "class_id": attendance.class_id.map_or(Bson::Null, Bson::ObjectId),
+                "session_id": Bson::Null,
+                "date": date.to_string(),
+            },
+        };
+
+        in_transaction!(self.collection.find_one(filter)).map_err(|err| {
+            error!(error = %err, "Error checking for attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_session"))]
+    async fn find_by_session(&self, session_id: ObjectId) -> Result<Vec<Attendance>, ErrorType> {
+        self.find_many(doc! { "session_id": session_id }).await
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "list"))]
+    async fn list(&self, query: &AttendanceListQuery) -> Result<Page<Attendance>, ErrorType> {
+        let mut conditions: Vec<Document> = Vec::new();
+        if let Some(student_id) = query.student_id {
+            conditions.push(doc! { "student_id": student_id });
+        }
+        if let Some(class_id) = query.class_id {
+            conditions.push(doc! { "class_id": class_id });
+        }
+        if let Some(classes) = &query.class_scope {
+            conditions.push(doc! { "class_id": { "$in": classes } });
+        }
+        if let Some(status) = query.status {
+            conditions.push(status_filter(status));
+        }
+        if let Some(range_filter) = range_filter(&query.range()) {
+            conditions.push(range_filter);
+        }
+
+        find_page(
+            &self.collection,
+            conditions,
+            query.sort.field_name(),
+            query.order,
+            query.cursor.as_deref(),
+            query.limit,
+            |attendance| Cursor {
+                value: query.sort.value_of(attendance),
+                id: attendance.id.unwrap_or_default(),
+            },
+        )
+        .await
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "summarize_student"))]
+    async fn summarize_student(
+        &self,
+        student_id: ObjectId,
+        range: &DateRange,
+        sessions_held: &BTreeMap<ObjectId, u64>,
+    ) -> Result<Vec<ClassAttendanceSummary>, ErrorType> {
+        let mut filter = doc! { "student_id": student_id };
+        if let Some(range_filter) = range_filter(range) {
+            filter.extend(range_filter);
+        }
+
+        let pipeline = vec![
+            doc! { "$match": filter },
+            // Legacy records only store a boolean flag instead of a status
+            doc! { "$addFields": {
+                "status": { "$ifNull": [
+                    "$status",
+                    { "$cond": [{ "$eq": ["$flag", false] }, "absent", "present"] },
+                ] },
+            } },
+            doc! { "$group": {
+                "_id": "$class_id",
+                "marked": { "$sum": 1 },
+                "without_session": { "$sum": {
+                    "$cond": [{ "$eq": [{ "$ifNull": ["$session_id", null] }, null] }, 1, 0],
+                } },
+                "attended": { "$sum": {
+                    "$cond": [{ "$in": ["$status", ["present", "late"]] }, 1, 0],
+                } },
+                "late": { "$sum": { "$cond": [{ "$eq": ["$status", "late"] }, 1, 0] } },
+                "excused": { "$sum": {
+                    "$cond": [
+                        { "$in": ["$status", ["excused", "medical_leave", "on_duty"]] },
+                        1,
+                        0,
+                    ],
+                } },
+            } },
+            doc! { "$project": {
+                "_id": 0,
+                "class_id": "$_id",
+                "marked": 1,
+                "without_session": 1,
+                "attended": 1,
+                "late": 1,
+                "excused": 1,
+            } },
+        ];
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .with_type::<RecordCounts>()
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error summarizing the attendance");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        let mut counts = Vec::new();
+        while let Some(class_counts) = cursor.try_next().await.map_err(|err| {
+            error!(error = %err, "Error summarizing the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })? {
+            counts.push(class_counts);
+        }
+
+        Ok(into_summaries(counts, sessions_held))
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_student"))]
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "student_id": student_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_session"))]
+    async fn count_by_session(&self, session_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "session_id": session_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_class"))]
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "class_id": class_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete_by_student"))]
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let delete_result = in_transaction!(
+            self.collection
+                .delete_many(doc! { "student_id": student_id })
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error deleting the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(delete_result.deleted_count)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "archive_by_student"))]
+    async fn archive_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let records = self.find_many(doc! { "student_id": student_id }).await?;
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        in_transaction!(self.archive.insert_many(&records)).map_err(|err| {
+            error!(error = ?err, "Error archiving the attendance");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        self.delete_by_student(student_id).await
+    }
+}
+
+/// Builds the MongoDB filter matching the records within a range of days: on their
+/// `date`, or on the day of their `time` for legacy records without a date.
+/// Returns `None` for an unbounded range.
+fn range_filter(range: &DateRange) -> Option<Document> {
+    if range.is_unbounded() {
+        return None;
+    }
+
+    let mut dates = Document::new();
+    let mut times = Document::new();
+    if let Some(from) = range.from {
+        dates.insert("$gte", from.to_string());
+        times.insert("$gte", start_of_day(from));
+    }
+    if let Some(to) = range.to {
+        dates.insert("$lte", to.to_string());
+        if let Some(next_day) = to.succ_opt() {
+            times.insert("$lt", start_of_day(next_day));
+        }
+    }
+
+    Some(doc! {
+        "$or": [
+            { "date": dates },
+            { "date": { "$exists": false }, "time": times },
+        ]
+    })
+}
+
+/// Builds the MongoDB filter matching the records with a status, reading the
+/// legacy `flag` of records without a status.
+fn status_filter(status: AttendanceStatus) -> Document {
+    let legacy = match status {
+        AttendanceStatus::Present => {
+            doc! { "status": { "$exists": false }, "flag": { "$ne": false } }
+        }
+        AttendanceStatus::Absent => doc! { "status": { "$exists": false }, "flag": false },
+        _ => return doc! { "status": status_name(status) },
+    };
+
+    doc! { "$or": [{ "status": status_name(status) }, legacy] }
+}
+
+/// Returns the name under which a status is stored.
+fn status_name(status: AttendanceStatus) -> Bson {
+    mongodb::bson::to_bson(&status).unwrap_or(Bson::Null)
+}
+
+/// Returns the timestamp, in seconds, of the start of a day in UTC, as the `time`
+/// field of the records is stored.
+fn start_of_day(date: NaiveDate) -> i64 {
+    date.and_time(NaiveTime::MIN).and_utc().timestamp()
+}
+
+/// Maps a failed insert to `ErrorType::AlreadyExists` when the unique index
+/// rejected it, and to `ErrorType::ServerError` otherwise.
+fn map_insert_error(err: mongodb::error::Error) -> ErrorType {
+    if is_duplicate_key_error(&err) {
+        return ErrorType::AlreadyExists("The attendance has already been marked".to_string());
+    }
+
+    error!(error = ?err, "Error inserting attendance");
+    ErrorType::ServerError("Server Error".to_string())
+}
+
+/// The counts of the records of a student in one class, before they are set
+/// against the sessions the class held.
+#[derive(Debug, Default, Deserialize)]
+struct RecordCounts {
+    class_id: Option<ObjectId>,
+    marked: u64,
+    without_session: u64,
+    attended: u64,
+    late: u64,
+    excused: u64,
+}
+
+/// Builds the summary of each class from the counts of the records and the
+/// sessions held, in order of class ID. A day marked without a session counts as
+/// one more held, and a class never holds fewer sessions than the student was
+/// marked for, so that the percentage stays within 0 and 100.
+fn into_summaries(
+    counts: Vec<RecordCounts>,
+    sessions_held: &BTreeMap<ObjectId, u64>,
+) -> Vec<ClassAttendanceSummary> {
+    let mut classes: BTreeMap<Option<ObjectId>, RecordCounts> = sessions_held
+        .keys()
+        .map(|&class_id| {
+            let counts = RecordCounts {
+                class_id: Some(class_id),
+                ..RecordCounts::default()
+            };
+            (Some(class_id), counts)
+        })
+        .collect();
+    for class_counts in counts {
+        classes.insert(class_counts.class_id, class_counts);
+    }
+
+    classes
+        .into_values()
+        .map(|counts| {
+            let held = counts
+                .class_id
+                .and_then(|class_id| sessions_held.get(&class_id))
+                .copied()
+                .unwrap_or_default();
+            let sessions_held = (held + counts.without_session).max(counts.marked);
+            let percentage = if sessions_held == 0 {
+                0.0
+            } else {
+                let ratio = counts.attended as f64 / sessions_held as f64;
+                (ratio * 10_000.0).round() / 100.0
+            };
+
+            ClassAttendanceSummary {
+                class_id: counts.class_id,
+                sessions_held,
+                marked: counts.marked,
+                attended: counts.attended,
+                late: counts.late,
+                excused: counts.excused,
+                percentage,
+            }
+        })
+        .collect()
+}
+
+/// An `AttendanceRepository` that keeps attendance records in memory. It is
+/// used to run the API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryAttendanceRepository {
+    records: RwLock<Vec<Attendance>>,
+    archive: RwLock<Vec<Attendance>>,
+}
+
+#[async_trait]
+impl AttendanceRepository for InMemoryAttendanceRepository {
+    async fn insert(&self, mut attendance: Attendance) -> Result<Attendance, ErrorType> {
+        let mut records = self.records.write().await;
+        if records
+            .iter()
+            .any(|record| record.is_same_mark(&attendance))
+        {
+            return Err(ErrorType::AlreadyExists(
+                "The attendance has already been marked".to_string(),
+            ));
+        }
+
+        attendance.id = Some(ObjectId::new());
+        records.push(attendance.clone());
+        Ok(attendance)
+    }
+
+    async fn insert_many(
+        &self,
+        mut attendances: Vec<Attendance>,
+    ) -> Result<Vec<Attendance>, ErrorType> {
+        let mut records = self.records.write().await;
+        for (index, attendance) in attendances.iter().enumerate() {
+            if records
+                .iter()
+                .chain(&attendances[..index])
+                .any(|record| record.is_same_mark(attendance))
+            {
+                return Err(ErrorType::AlreadyExists(
+                    "The attendance has already been marked".to_string(),
+                ));
+            }
+        }
+
+        for attendance in &mut attendances {
+            attendance.id = Some(ObjectId::new());
+        }
+
+        records.extend(attendances.iter().cloned());
+        Ok(attendances)
+    }
+
+    async fn find_duplicate(
+        &self,
+        attendance: &Attendance,
+    ) -> Result<Option<Attendance>, ErrorType> {
+        let records = self.records.read().await;
+        Ok(records
+            .iter()
+            .find(|record| record.is_same_mark(attendance))
+            .cloned())
+    }
+
+    async fn find_by_session(&self, session_id: ObjectId) -> Result<Vec<Attendance>, ErrorType> {
+        let records = self.records.read().await;
+        Ok(records
+            .iter()
+            .filter(|record| record.session_id == Some(session_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn list(&self, query: &AttendanceListQuery) -> Result<Page<Attendance>, ErrorType> {
+        let limit = page_limit(query.limit);
+        let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+
+        let mut attendances: Vec<Attendance> = self
+            .records
+            .read()
+            .await
+            .iter()
+            .filter(|record| query.matches(record))
+            .filter(|record| {
+                cursor.as_ref().is_none_or(|cursor| {
+                    cursor.is_followed_by(
+                        &query.sort.value_of(record),
+                        record.id.unwrap_or_default(),
+                        query.order,
+                    )
+                })
+            })
+            .cloned()
+            .collect();
+
+        attendances.sort_by(|a, b| {
+            let ordering = compare_bson(&query.sort.value_of(a), &query.sort.value_of(b))
+                .then_with(|| a.id.cmp(&b.id));
+            query.order.apply(ordering)
+        });
+        attendances.truncate(limit as usize + 1);
+
+        Ok(into_page(attendances, limit, |attendance| Cursor {
+            value: query.sort.value_of(attendance),
+            id: attendance.id.unwrap_or_default(),
+        }))
+    }
+
+    async fn summarize_student(
+        &self,
+        student_id: ObjectId,
+        range: &DateRange,
+        sessions_held: &BTreeMap<ObjectId, u64>,
+    ) -> Result<Vec<ClassAttendanceSummary>, ErrorType> {
+        let records = self.records.read().await;
+
+        let mut classes: BTreeMap<Option<ObjectId>, RecordCounts> = BTreeMap::new();
+        for record in records
+            .iter()
+            .filter(|record| record.student_id == student_id && range.contains(record.day()))
+        {
+            let counts = classes
+                .entry(record.class_id)
+                .or_insert_with(|| RecordCounts {
+                    class_id: record.class_id,
+                    ..RecordCounts::default()
+                });
+
+            counts.marked += 1;
+            counts.without_session += u64::from(record.session_id.is_none());
+            counts.attended += u64::from(record.status.is_attended());
+            counts.late += u64::from(record.status == AttendanceStatus::Late);
+            counts.excused += u64::from(record.status.is_excused());
+        }
+
+        Ok(into_summaries(
+            classes.into_values().collect(),
+            sessions_held,
+        ))
+    }
+
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let records = self.records.read().await;
+        Ok(records
+            .iter()
+            .filter(|record| record.student_id == student_id)
+            .count() as u64)
+    }
+
+    async fn count_by_session(&self, session_id: ObjectId) -> Result<u64, ErrorType> {
+        let records = self.records.read().await;
+        Ok(records
+            .iter()
+            .filter(|record| record.session_id == Some(session_id))
+            .count() as u64)
+    }
+
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        let records = self.records.read().await;
+        Ok(records
+            .iter()
+            .filter(|record| record.class_id == Some(class_id))
+            .count() as u64)
+    }
+
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let mut records = self.records.write().await;
+        let len_before = records.len();
+        records.retain(|record| record.student_id != student_id);
+        Ok((len_before - records.len()) as u64)
+    }
+
+    async fn archive_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let mut records = self.records.write().await;
+        let (archived, kept) = records
+            .drain(..)
+            .partition::<Vec<_>, _>(|record| record.student_id == student_id);
+        *records = kept;
+
+        let count = archived.len() as u64;
+        self.archive.write().await.extend(archived);
+        Ok(count)
+    }
+}
@@ -0,0 +1,297 @@
+//! This module defines the `ClassRepository` trait and its implementations.
+
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::models::class_model::{Class, ClassListQuery};
+use crate::models::page_model::escape_regex;
+use crate::transaction::in_transaction;
+use async_trait::async_trait;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Bson, Document, doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use tokio::sync::RwLock;
+use tokio_stream::StreamExt;
+use tracing::{error, info, instrument};
+
+/// Abstracts the storage of classes, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait ClassRepository: Send + Sync {
+    /// Inserts a new class and returns it with its generated ID. Fails with
+    /// `ErrorType::AlreadyExists` if another class has the same name.
+    async fn insert(&self, class: Class) -> Result<Class, ErrorType>;
+
+    /// Finds a class by its ID.
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Class>, ErrorType>;
+
+    /// Replaces the stored class having the same ID. Returns `false` if no such
+    /// class exists. Fails with `ErrorType::AlreadyExists` if another class has the
+    /// same name.
+    async fn update(&self, class: &Class) -> Result<bool, ErrorType>;
+
+    /// Deletes a class by its ID. Returns `false` if no such class exists.
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType>;
+
+    /// Returns the classes matching the query, in the order they were added.
+    async fn list(&self, query: &ClassListQuery) -> Result<Vec<Class>, ErrorType>;
+}
+
+/// A `ClassRepository` backed by the MongoDB `classes` collection.
+pub struct MongoClassRepository {
+    collection: Collection<Class>,
+}
+
+impl MongoClassRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `classes` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the unique index on the name, which keeps two classes from sharing
+    /// one even when they are added at the same time.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let index = IndexModel::builder()
+            .keys(doc! { "name": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_name".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+
+        self.collection.create_index(index).await.map_err(|err| {
+            error!(error = %err, "Error creating the class indexes");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClassRepository for MongoClassRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut class: Class) -> Result<Class, ErrorType> {
+        class.id = None;
+
+        let insert_result = in_transaction!(self.collection.insert_one(&class))
+            .map_err(|err| map_write_error(err, "Error inserting class"))?;
+
+        class.id = insert_result.inserted_id.as_object_id();
+        Ok(class)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_id"))]
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Class>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "_id": id })).map_err(|err| {
+            error!(error = %err, "Error checking for existing class");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, class: &Class) -> Result<bool, ErrorType> {
+        let update_result =
+            in_transaction!(self.collection.replace_one(doc! { "_id": class.id }, class))
+                .map_err(|err| map_write_error(err, "Error updating class"))?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete"))]
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let delete_result = in_transaction!(self.collection.delete_one(doc! { "_id": id }))
+            .map_err(|err| {
+                error!(error = ?err, "Error deleting class");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(delete_result.deleted_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "list"))]
+    async fn list(&self, query: &ClassListQuery) -> Result<Vec<Class>, ErrorType> {
+        // Classes stored before they could be archived have no `archived` field.
+        let mut conditions: Vec<Document> = vec![if query.archived {
+            doc! { "archived": true }
+        } else {
+            doc! { "archived": { "$ne": true } }
+        }];
+        if let Some(search) = query.search.as_deref().filter(|search| !search.is_empty()) {
+            let pattern = escape_regex(search);
+            conditions.push(doc! {
+                "$or": [
+                    { "name": { "$regex": &pattern, "$options": "i" } },
+                    { "course_code": { "$regex": &pattern, "$options": "i" } },
+                ]
+            });
+        }
+        if let Some(course_code) = &query.course_code {
+            conditions.push(doc! { "course_code": course_code });
+        }
+        if let Some(department) = &query.department {
+            conditions.push(doc! { "department": department });
+        }
+        if let Some(term) = &query.term {
+            conditions.push(doc! { "term": term });
+        }
+        if let Some(credits) = query.credits {
+            conditions.push(doc! { "credits": credits });
+        }
+        if let Some(min_capacity) = query.min_capacity {
+            conditions.push(doc! { "capacity": { "$gte": min_capacity } });
+        }
+        if let Some(weekday) = query.weekday {
+            let weekday = mongodb::bson::to_bson(&weekday).unwrap_or(Bson::Null);
+            conditions.push(doc! { "schedule.weekday": weekday });
+        }
+
+        let mut cursor = self
+            .collection
+            .find(doc! { "$and": conditions })
+            .sort(doc! { "_id": 1 })
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error fetching the classes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        let mut classes = Vec::new();
+        while let Some(class) = cursor.try_next().await.map_err(|err| {
+            error!(error = %err, "Error fetching the classes");
+            ErrorType::ServerError("Server Error".to_string())
+        })? {
+            classes.push(class);
+        }
+
+        Ok(classes)
+    }
+}
+
+/// Maps a failed write to `ErrorType::AlreadyExists` when the unique index on the
+/// name rejected it, and to `ErrorType::ServerError` otherwise.
+fn map_write_error(err: mongodb::error::Error, message: &str) -> ErrorType {
+    if is_duplicate_key_error(&err) {
+        info!("Class already exists");
+        return name_taken();
+    }
+
+    error!(error = ?err, "{}", message);
+    ErrorType::ServerError("Server Error".to_string())
+}
+
+/// The error of a class whose name already belongs to another class.
+fn name_taken() -> ErrorType {
+    ErrorType::AlreadyExists("The class already exists".to_string())
+}
+
+/// A `ClassRepository` that keeps classes in memory. It is used to run the
+/// API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryClassRepository {
+    classes: RwLock<Vec<Class>>,
+}
+
+#[async_trait]
+impl ClassRepository for InMemoryClassRepository {
+    async fn insert(&self, mut class: Class) -> Result<Class, ErrorType> {
+        let mut classes = self.classes.write().await;
+        if classes.iter().any(|stored| stored.name == class.name) {
+            return Err(name_taken());
+        }
+
+        class.id = Some(ObjectId::new());
+        classes.push(class.clone());
+        Ok(class)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Class>, ErrorType> {
+        let classes = self.classes.read().await;
+        Ok(classes.iter().find(|class| class.id == Some(id)).cloned())
+    }
+
+    async fn update(&self, class: &Class) -> Result<bool, ErrorType> {
+        let mut classes = self.classes.write().await;
+        if classes
+            .iter()
+            .any(|stored| stored.id != class.id && stored.name == class.name)
+        {
+            return Err(name_taken());
+        }
+
+        match classes.iter_mut().find(|stored| stored.id == class.id) {
+            Some(stored) => {
+                *stored = class.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let mut classes = self.classes.write().await;
+        let len_before = classes.len();
+        classes.retain(|class| class.id != Some(id));
+        Ok(classes.len() < len_before)
+    }
+
+    async fn list(&self, query: &ClassListQuery) -> Result<Vec<Class>, ErrorType> {
+        let search = query.search.as_deref().unwrap_or_default().to_lowercase();
+
+        Ok(self
+            .classes
+            .read()
+            .await
+            .iter()
+            .filter(|class| class.archived == query.archived)
+            .filter(|class| {
+                class.name.to_lowercase().contains(&search)
+                    || class
+                        .course_code
+                        .as_deref()
+                        .is_some_and(|code| code.to_lowercase().contains(&search))
+            })
+            .filter(|class| {
+                query
+                    .course_code
+                    .as_ref()
+                    .is_none_or(|code| class.course_code.as_ref() == Some(code))
+            })
+            .filter(|class| {
+                query
+                    .department
+                    .as_ref()
+                    .is_none_or(|department| class.department.as_ref() == Some(department))
+            })
+            .filter(|class| {
+                query
+                    .term
+                    .as_ref()
+                    .is_none_or(|term| class.term.as_ref() == Some(term))
+            })
+            .filter(|class| {
+                query
+                    .credits
+                    .is_none_or(|credits| class.credits == Some(credits))
+            })
+            .filter(|class| {
+                query
+                    .min_capacity
+                    .is_none_or(|min| class.capacity.is_some_and(|capacity| capacity >= min))
+            })
+            .filter(|class| {
+                query
+                    .weekday
+                    .is_none_or(|weekday| class.schedule.iter().any(|slot| slot.weekday == weekday))
+            })
+            .cloned()
+            .collect())
+    }
+}
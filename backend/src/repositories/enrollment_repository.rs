@@ -0,0 +1,342 @@
+//! This module defines the `EnrollmentRepository` trait and its implementations.
+
+use crate::error::ErrorType;
+use crate::models::enrollment_model::Enrollment;
+use crate::transaction::{collect, in_transaction};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Document, doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use tokio::sync::RwLock;
+use tracing::{error, instrument};
+
+/// Abstracts the storage of enrollments, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait EnrollmentRepository: Send + Sync {
+    /// Inserts a new enrollment and returns it with its generated ID.
+    async fn insert(&self, enrollment: Enrollment) -> Result<Enrollment, ErrorType>;
+
+    /// Returns every enrollment, past or present, of a student.
+    async fn find_by_student(&self, student_id: ObjectId) -> Result<Vec<Enrollment>, ErrorType>;
+
+    /// Returns every enrollment, past or present, of a student in a class.
+    async fn find_by_student_and_class(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Vec<Enrollment>, ErrorType>;
+
+    /// Finds the enrollment of a student in a class that is effective on the given date.
+    async fn find_active(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Option<Enrollment>, ErrorType>;
+
+    /// Returns the enrollments of a class that are effective on the given date.
+    async fn find_active_in_class(
+        &self,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Vec<Enrollment>, ErrorType>;
+
+    /// Replaces the stored enrollment having the same ID. Returns `false` if no
+    /// such enrollment exists.
+    async fn update(&self, enrollment: &Enrollment) -> Result<bool, ErrorType>;
+
+    /// Deletes an enrollment by its ID. Returns `false` if no such enrollment exists.
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType>;
+
+    /// Deletes all the enrollments of a student and returns how many were removed.
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Counts the enrollments, past or present, of a class.
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType>;
+}
+
+/// An `EnrollmentRepository` backed by the MongoDB `enrollments` collection.
+pub struct MongoEnrollmentRepository {
+    collection: Collection<Enrollment>,
+}
+
+impl MongoEnrollmentRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `enrollments` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the indexes of the lookups by student, which also serves the lookups
+    /// by student and class, and by class.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let by_student = IndexModel::builder()
+            .keys(doc! { "student_id": 1, "class_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("student_id_class_id".to_string())
+                    .build(),
+            )
+            .build();
+        let by_class = IndexModel::builder()
+            .keys(doc! { "class_id": 1 })
+            .options(IndexOptions::builder().name("class_id".to_string()).build())
+            .build();
+
+        self.collection
+            .create_indexes([by_student, by_class])
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error creating the enrollment indexes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(())
+    }
+
+    /// Collects every enrollment matching the filter.
+    async fn find_many(&self, filter: Document) -> Result<Vec<Enrollment>, ErrorType> {
+        collect(self.collection.find(filter)).await.map_err(|err| {
+            error!(error = %err, "Error fetching the enrollments");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+}
+
+/// Builds the MongoDB filter matching the enrollments effective on the given date.
+/// Dates are stored as `YYYY-MM-DD` strings, which sort in calendar order.
+fn active_on(date: NaiveDate) -> Document {
+    let date = date.to_string();
+    doc! {
+        "start_date": { "$lte": &date },
+        "$or": [
+            { "end_date": null },
+            { "end_date": { "$gte": &date } },
+        ],
+    }
+}
+
+#[async_trait]
+impl EnrollmentRepository for MongoEnrollmentRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut enrollment: Enrollment) -> Result<Enrollment, ErrorType> {
+        enrollment.id = None;
+
+        let insert_result =
+            in_transaction!(self.collection.insert_one(&enrollment)).map_err(|err| {
+                error!(error = ?err, "Error inserting enrollment");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        enrollment.id = insert_result.inserted_id.as_object_id();
+        Ok(enrollment)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_student"))]
+    async fn find_by_student(&self, student_id: ObjectId) -> Result<Vec<Enrollment>, ErrorType> {
+        self.find_many(doc! { "student_id": student_id }).await
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_student_and_class"))]
+    async fn find_by_student_and_class(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Vec<Enrollment>, ErrorType> {
+        self.find_many(doc! { "student_id": student_id, "class_id": class_id })
+            .await
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_active"))]
+    async fn find_active(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Option<Enrollment>, ErrorType> {
+        let mut filter = active_on(date);
+        filter.insert("student_id", student_id);
+        filter.insert("class_id", class_id);
+
+        in_transaction!(self.collection.find_one(filter)).map_err(|err| {
+            error!(error = %err, "Error checking for enrollment");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_active_in_class"))]
+    async fn find_active_in_class(
+        &self,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Vec<Enrollment>, ErrorType> {
+        let mut filter = active_on(date);
+        filter.insert("class_id", class_id);
+
+        self.find_many(filter).await
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, enrollment: &Enrollment) -> Result<bool, ErrorType> {
+        let update_result = in_transaction!(
+            self.collection
+                .replace_one(doc! { "_id": enrollment.id }, enrollment)
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error updating enrollment");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete"))]
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let delete_result = in_transaction!(self.collection.delete_one(doc! { "_id": id }))
+            .map_err(|err| {
+                error!(error = ?err, "Error deleting enrollment");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(delete_result.deleted_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete_by_student"))]
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let delete_result = in_transaction!(
+            self.collection
+                .delete_many(doc! { "student_id": student_id })
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error deleting the enrollments");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(delete_result.deleted_count)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_class"))]
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "class_id": class_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the enrollments");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+}
+
+/// An `EnrollmentRepository` that keeps enrollments in memory. It is used to run
+/// the API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryEnrollmentRepository {
+    enrollments: RwLock<Vec<Enrollment>>,
+}
+
+#[async_trait]
+impl EnrollmentRepository for InMemoryEnrollmentRepository {
+    async fn insert(&self, mut enrollment: Enrollment) -> Result<Enrollment, ErrorType> {
+        enrollment.id = Some(ObjectId::new());
+        self.enrollments.write().await.push(enrollment.clone());
+        Ok(enrollment)
+    }
+
+    async fn find_by_student(&self, student_id: ObjectId) -> Result<Vec<Enrollment>, ErrorType> {
+        let enrollments = self.enrollments.read().await;
+        Ok(enrollments
+            .iter()
+            .filter(|enrollment| enrollment.student_id == student_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn find_by_student_and_class(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Vec<Enrollment>, ErrorType> {
+        let enrollments = self.enrollments.read().await;
+        Ok(enrollments
+            .iter()
+            .filter(|enrollment| {
+                enrollment.student_id == student_id && enrollment.class_id == class_id
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn find_active(
+        &self,
+        student_id: ObjectId,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Option<Enrollment>, ErrorType> {
+        let enrollments = self.enrollments.read().await;
+        Ok(enrollments
+            .iter()
+            .find(|enrollment| {
+                enrollment.student_id == student_id
+                    && enrollment.class_id == class_id
+                    && enrollment.is_active_on(date)
+            })
+            .cloned())
+    }
+
+    async fn find_active_in_class(
+        &self,
+        class_id: ObjectId,
+        date: NaiveDate,
+    ) -> Result<Vec<Enrollment>, ErrorType> {
+        let enrollments = self.enrollments.read().await;
+        Ok(enrollments
+            .iter()
+            .filter(|enrollment| enrollment.class_id == class_id && enrollment.is_active_on(date))
+            .cloned()
+            .collect())
+    }
+
+    async fn update(&self, enrollment: &Enrollment) -> Result<bool, ErrorType> {
+        let mut enrollments = self.enrollments.write().await;
+        match enrollments
+            .iter_mut()
+            .find(|stored| stored.id == enrollment.id)
+        {
+            Some(stored) => {
+                *stored = enrollment.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let mut enrollments = self.enrollments.write().await;
+        let len_before = enrollments.len();
+        enrollments.retain(|enrollment| enrollment.id != Some(id));
+        Ok(enrollments.len() < len_before)
+    }
+
+    async fn delete_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let mut enrollments = self.enrollments.write().await;
+        let len_before = enrollments.len();
+        enrollments.retain(|enrollment| enrollment.student_id != student_id);
+        Ok((len_before - enrollments.len()) as u64)
+    }
+
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        let enrollments = self.enrollments.read().await;
+        Ok(enrollments
+            .iter()
+            .filter(|enrollment| enrollment.class_id == class_id)
+            .count() as u64)
+    }
+}
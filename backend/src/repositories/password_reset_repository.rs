@@ -0,0 +1,179 @@
+//! This module defines the `PasswordResetRepository` trait and its implementations.
+
+use crate::error::ErrorType;
+use crate::models::user_model::PasswordReset;
+use crate::transaction::in_transaction;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{self, doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, instrument};
+
+/// Abstracts the storage of password-reset tokens, so that the routes do not
+/// depend on a particular database.
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    /// Inserts a new reset token and returns it with its generated ID.
+    async fn insert(&self, reset: PasswordReset) -> Result<PasswordReset, ErrorType>;
+
+    /// Marks the token with the given hash as used at `now` and returns it, in a
+    /// single atomic step. Returns `None` if no such token exists, or if it has
+    /// already been used or has expired.
+    async fn consume(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<PasswordReset>, ErrorType>;
+
+    /// Deletes every token of the given user and returns how many were deleted.
+    async fn delete_by_user(&self, user_id: ObjectId) -> Result<u64, ErrorType>;
+}
+
+/// A `PasswordResetRepository` backed by the MongoDB `password_resets` collection.
+pub struct MongoPasswordResetRepository {
+    collection: Collection<PasswordReset>,
+}
+
+impl MongoPasswordResetRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `password_resets` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the unique index on the token hash, which keeps a token from matching
+    /// two resets, and the TTL index that deletes the tokens once they expire.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let unique_token_hash = IndexModel::builder()
+            .keys(doc! { "token_hash": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_token_hash".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+        let expiry = IndexModel::builder()
+            .keys(doc! { "expires_at": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("expires_at".to_string())
+                    .expire_after(Duration::ZERO)
+                    .build(),
+            )
+            .build();
+
+        self.collection
+            .create_indexes([unique_token_hash, expiry])
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error creating the password reset indexes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepository for MongoPasswordResetRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut reset: PasswordReset) -> Result<PasswordReset, ErrorType> {
+        reset.id = None;
+
+        let insert_result = in_transaction!(self.collection.insert_one(&reset)).map_err(|err| {
+            error!(error = ?err, "Error inserting password reset");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        reset.id = insert_result.inserted_id.as_object_id();
+        Ok(reset)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "consume"))]
+    async fn consume(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<PasswordReset>, ErrorType> {
+        let now_date = bson::DateTime::from_chrono(now);
+        let reset = in_transaction!(self.collection.find_one_and_update(
+            doc! {
+                "token_hash": token_hash,
+                "used_at": null,
+                "expires_at": { "$gt": now_date },
+            },
+            doc! { "$set": { "used_at": now_date } },
+        ))
+        .map_err(|err| {
+            error!(error = ?err, "Error consuming password reset");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(reset.map(|reset| PasswordReset {
+            used_at: Some(now),
+            ..reset
+        }))
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete_by_user"))]
+    async fn delete_by_user(&self, user_id: ObjectId) -> Result<u64, ErrorType> {
+        let delete_result = in_transaction!(
+            self.collection.delete_many(doc! { "user_id": user_id })
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error deleting the password resets of user");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(delete_result.deleted_count)
+    }
+}
+
+/// A `PasswordResetRepository` that keeps reset tokens in memory. It is used to
+/// run the API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryPasswordResetRepository {
+    resets: RwLock<Vec<PasswordReset>>,
+}
+
+#[async_trait]
+impl PasswordResetRepository for InMemoryPasswordResetRepository {
+    async fn insert(&self, mut reset: PasswordReset) -> Result<PasswordReset, ErrorType> {
+        reset.id = Some(ObjectId::new());
+        self.resets.write().await.push(reset.clone());
+        Ok(reset)
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<PasswordReset>, ErrorType> {
+        let mut resets = self.resets.write().await;
+        let Some(reset) = resets
+            .iter_mut()
+            .find(|reset| reset.token_hash == token_hash && reset.is_usable_at(now))
+        else {
+            return Ok(None);
+        };
+
+        reset.used_at = Some(now);
+        Ok(Some(reset.clone()))
+    }
+
+    async fn delete_by_user(&self, user_id: ObjectId) -> Result<u64, ErrorType> {
+        let mut resets = self.resets.write().await;
+        let count = resets.len();
+        resets.retain(|reset| reset.user_id != user_id);
+        Ok((count - resets.len()) as u64)
+    }
+}
@@ -0,0 +1,247 @@
+//! This module defines the `SessionRepository` trait and its implementations.
+
+use crate::error::ErrorType;
+use crate::models::session_model::Session;
+use crate::transaction::{collect, in_transaction};
+use async_trait::async_trait;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use tokio::sync::RwLock;
+use tracing::{error, instrument};
+
+/// Abstracts the storage of class sessions, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    /// Inserts a new session and returns it with its generated ID.
+    async fn insert(&self, session: Session) -> Result<Session, ErrorType>;
+
+    /// Finds a session by its ID.
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Session>, ErrorType>;
+
+    /// Returns all the sessions of a class, in order of start time.
+    async fn find_by_class(&self, class_id: ObjectId) -> Result<Vec<Session>, ErrorType>;
+
+    /// Replaces the stored session having the same ID. Returns `false` if no such
+    /// session exists.
+    async fn update(&self, session: &Session) -> Result<bool, ErrorType>;
+
+    /// Deletes a session by its ID. Returns `false` if no such session exists.
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType>;
+
+    /// Counts the sessions taken by a teacher.
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Counts the sessions of a class.
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType>;
+}
+
+/// A `SessionRepository` backed by the MongoDB `sessions` collection.
+pub struct MongoSessionRepository {
+    collection: Collection<Session>,
+}
+
+impl MongoSessionRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `sessions` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the index of the lookups by class, which also returns the sessions in
+    /// order of start time, and the index of the lookups by teacher.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let by_class = IndexModel::builder()
+            .keys(doc! { "class_id": 1, "start_time": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("class_id_start_time".to_string())
+                    .build(),
+            )
+            .build();
+        let by_teacher = IndexModel::builder()
+            .keys(doc! { "teacher_id": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("teacher_id".to_string())
+                    .build(),
+            )
+            .build();
+
+        self.collection
+            .create_indexes([by_class, by_teacher])
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error creating the session indexes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MongoSessionRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut session: Session) -> Result<Session, ErrorType> {
+        session.id = None;
+
+        let insert_result =
+            in_transaction!(self.collection.insert_one(&session)).map_err(|err| {
+                error!(error = ?err, "Error inserting session");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        session.id = insert_result.inserted_id.as_object_id();
+        Ok(session)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_id"))]
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Session>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "_id": id })).map_err(|err| {
+            error!(error = %err, "Error checking for session id");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_class"))]
+    async fn find_by_class(&self, class_id: ObjectId) -> Result<Vec<Session>, ErrorType> {
+        collect(
+            self.collection
+                .find(doc! { "class_id": class_id })
+                .sort(doc! { "start_time": 1 }),
+        )
+        .await
+        .map_err(|err| {
+            error!(error = %err, "Error fetching the sessions");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, session: &Session) -> Result<bool, ErrorType> {
+        let update_result = in_transaction!(
+            self.collection
+                .replace_one(doc! { "_id": session.id }, session)
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error updating session");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete"))]
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let delete_result = in_transaction!(self.collection.delete_one(doc! { "_id": id }))
+            .map_err(|err| {
+                error!(error = ?err, "Error deleting session");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(delete_result.deleted_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_teacher"))]
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "teacher_id": teacher_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the sessions of teacher");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_class"))]
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "class_id": class_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the sessions");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+}
+
+/// A `SessionRepository` that keeps sessions in memory. It is used to run the API
+/// without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemorySessionRepository {
+    sessions: RwLock<Vec<Session>>,
+}
+
+#[async_trait]
+impl SessionRepository for InMemorySessionRepository {
+    async fn insert(&self, mut session: Session) -> Result<Session, ErrorType> {
+        session.id = Some(ObjectId::new());
+        self.sessions.write().await.push(session.clone());
+        Ok(session)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Session>, ErrorType> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions
+            .iter()
+            .find(|session| session.id == Some(id))
+            .cloned())
+    }
+
+    async fn find_by_class(&self, class_id: ObjectId) -> Result<Vec<Session>, ErrorType> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .read()
+            .await
+            .iter()
+            .filter(|session| session.class_id == class_id)
+            .cloned()
+            .collect();
+
+        sessions.sort_by_key(|session| session.start_time);
+        Ok(sessions)
+    }
+
+    async fn update(&self, session: &Session) -> Result<bool, ErrorType> {
+        let mut sessions = self.sessions.write().await;
+        match sessions.iter_mut().find(|stored| stored.id == session.id) {
+            Some(stored) => {
+                *stored = session.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let mut sessions = self.sessions.write().await;
+        let len_before = sessions.len();
+        sessions.retain(|session| session.id != Some(id));
+        Ok(sessions.len() < len_before)
+    }
+
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions
+            .iter()
+            .filter(|session| session.teacher_id == Some(teacher_id))
+            .count() as u64)
+    }
+
+    async fn count_by_class(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        let sessions = self.sessions.read().await;
+        Ok(sessions
+            .iter()
+            .filter(|session| session.class_id == class_id)
+            .count() as u64)
+    }
+}
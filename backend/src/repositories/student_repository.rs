@@ -0,0 +1,322 @@
+//! This module defines the `StudentRepository` trait and its implementations.
+
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::models::page_model::{
+    Cursor, Page, compare_bson, escape_regex, find_page, into_page, page_limit,
+};
+use crate::models::student_model::{Student, StudentListQuery};
+use crate::transaction::{Transaction, in_transaction};
+use async_trait::async_trait;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Document, doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use tokio::sync::RwLock;
+use tracing::{error, info, instrument};
+
+/// Abstracts the storage of students, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait StudentRepository: Send + Sync {
+    /// Inserts a new student and returns it with its generated ID. Fails with
+    /// `ErrorType::AlreadyExists` if another student has the same email.
+    async fn insert(&self, student: Student) -> Result<Student, ErrorType>;
+
+    /// Finds a student by their ID.
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Student>, ErrorType>;
+
+    /// Replaces the stored student having the same ID. Returns `false` if no
+    /// such student exists. Fails with `ErrorType::AlreadyExists` if another
+    /// student has the same email.
+    async fn update(&self, student: &Student) -> Result<bool, ErrorType>;
+
+    /// Locks the given students for the rest of the current transaction, so that a
+    /// concurrent transaction deleting one of them conflicts with this one instead
+    /// of leaving records that point to a deleted student. Returns how many of the
+    /// students exist.
+    async fn lock(&self, ids: &[ObjectId]) -> Result<u64, ErrorType>;
+
+    /// Deletes a student by their ID. Returns `false` if no such student exists.
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType>;
+
+    /// Returns a page of the students matching the query.
+    async fn list(&self, query: &StudentListQuery) -> Result<Page<Student>, ErrorType>;
+}
+
+/// A `StudentRepository` backed by the MongoDB `students` collection.
+pub struct MongoStudentRepository {
+    collection: Collection<Student>,
+}
+
+impl MongoStudentRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `students` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the unique index on the email, which keeps two students from
+    /// sharing one even when they are added at the same time.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let index = IndexModel::builder()
+            .keys(doc! { "email": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_email".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+
+        self.collection.create_index(index).await.map_err(|err| {
+            error!(error = %err, "Error creating the student indexes");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StudentRepository for MongoStudentRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut student: Student) -> Result<Student, ErrorType> {
+        student.id = None;
+
+        let insert_result = in_transaction!(self.collection.insert_one(&student))
+            .map_err(|err| map_write_error(err, "Error inserting student"))?;
+
+        student.id = insert_result.inserted_id.as_object_id();
+        Ok(student)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_id"))]
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Student>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "_id": id })).map_err(|err| {
+            error!(error = %err, "Error checking for student id");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, student: &Student) -> Result<bool, ErrorType> {
+        let update_result = in_transaction!(
+            self.collection
+                .replace_one(doc! { "_id": student.id }, student)
+        )
+        .map_err(|err| map_write_error(err, "Error updating student"))?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "lock"))]
+    async fn lock(&self, ids: &[ObjectId]) -> Result<u64, ErrorType> {
+        let filter = doc! { "_id": { "$in": ids } };
+
+        // Outside of a transaction there is nothing to lock, and only the students
+        // are counted.
+        if Transaction::current().is_none() {
+            return self
+                .collection
+                .count_documents(filter)
+                .await
+                .map_err(|err| {
+                    error!(error = %err, "Error checking for student id");
+                    ErrorType::ServerError("Server Error".to_string())
+                });
+        }
+
+        // Writing to the students is what makes a concurrent write to them conflict.
+        // The field is removed again in the same transaction, so the students are
+        // left as they were; a write that changes nothing would not lock them.
+        let lock_error = |err: mongodb::error::Error| {
+            error!(error = ?err, "Error locking students");
+            ErrorType::ServerError("Server Error".to_string())
+        };
+        let update_result = in_transaction!(
+            self.collection
+                .update_many(filter.clone(), doc! { "$set": { "lock": ObjectId::new() } })
+        )
+        .map_err(lock_error)?;
+        in_transaction!(
+            self.collection
+                .update_many(filter, doc! { "$unset": { "lock": "" } })
+        )
+        .map_err(lock_error)?;
+
+        Ok(update_result.matched_count)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete"))]
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let delete_result = in_transaction!(self.collection.delete_one(doc! { "_id": id }))
+            .map_err(|err| {
+                error!(error = ?err, "Error deleting student");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(delete_result.deleted_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "list"))]
+    async fn list(&self, query: &StudentListQuery) -> Result<Page<Student>, ErrorType> {
+        let mut conditions: Vec<Document> = Vec::new();
+        if let Some(search) = query.search.as_deref().filter(|search| !search.is_empty()) {
+            let pattern = escape_regex(search);
+            conditions.push(doc! {
+                "$or": [
+                    { "name": { "$regex": &pattern, "$options": "i" } },
+                    { "email": { "$regex": &pattern, "$options": "i" } },
+                ]
+            });
+        }
+        if let Some(roll_min) = query.roll_min {
+            conditions.push(doc! { "roll_number": { "$gte": roll_min } });
+        }
+        if let Some(roll_max) = query.roll_max {
+            conditions.push(doc! { "roll_number": { "$lte": roll_max } });
+        }
+
+        find_page(
+            &self.collection,
+            conditions,
+            query.sort.field_name(),
+            query.order,
+            query.cursor.as_deref(),
+            query.limit,
+            |student| Cursor {
+                value: query.sort.value_of(student),
+                id: student.id.unwrap_or_default(),
+            },
+        )
+        .await
+    }
+}
+
+/// Maps a failed write to `ErrorType::AlreadyExists` when the unique index on the
+/// email rejected it, and to `ErrorType::ServerError` otherwise.
+fn map_write_error(err: mongodb::error::Error, message: &str) -> ErrorType {
+    if is_duplicate_key_error(&err) {
+        info!("Email already exists");
+        return email_taken();
+    }
+
+    error!(error = ?err, "{}", message);
+    ErrorType::ServerError("Server Error".to_string())
+}
+
+/// The error of a student whose email already belongs to another student.
+fn email_taken() -> ErrorType {
+    ErrorType::AlreadyExists("Student with email already exists".to_string())
+}
+
+/// A `StudentRepository` that keeps students in memory. It is used to run the
+/// API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryStudentRepository {
+    students: RwLock<Vec<Student>>,
+}
+
+#[async_trait]
+impl StudentRepository for InMemoryStudentRepository {
+    async fn insert(&self, mut student: Student) -> Result<Student, ErrorType> {
+        let mut students = self.students.write().await;
+        if students.iter().any(|stored| stored.email == student.email) {
+            return Err(email_taken());
+        }
+
+        student.id = Some(ObjectId::new());
+        students.push(student.clone());
+        Ok(student)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Student>, ErrorType> {
+        let students = self.students.read().await;
+        Ok(students
+            .iter()
+            .find(|student| student.id == Some(id))
+            .cloned())
+    }
+
+    async fn update(&self, student: &Student) -> Result<bool, ErrorType> {
+        let mut students = self.students.write().await;
+        if students
+            .iter()
+            .any(|stored| stored.id != student.id && stored.email == student.email)
+        {
+            return Err(email_taken());
+        }
+
+        match students.iter_mut().find(|stored| stored.id == student.id) {
+            Some(stored) => {
+                *stored = student.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn lock(&self, ids: &[ObjectId]) -> Result<u64, ErrorType> {
+        // The multi-step writes of the in-memory storage run one at a time (see
+        // `Transactions::disabled`), so nothing else can delete the students until
+        // the current one ends, and they are only counted.
+        let students = self.students.read().await;
+        Ok(students
+            .iter()
+            .filter(|student| student.id.is_some_and(|id| ids.contains(&id)))
+            .count() as u64)
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let mut students = self.students.write().await;
+        let len_before = students.len();
+        students.retain(|student| student.id != Some(id));
+        Ok(students.len() < len_before)
+    }
+
+    async fn list(&self, query: &StudentListQuery) -> Result<Page<Student>, ErrorType> {
+        let limit = page_limit(query.limit);
+        let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+        let search = query.search.as_deref().unwrap_or_default().to_lowercase();
+
+        let mut students: Vec<Student> = self
+            .students
+            .read()
+            .await
+            .iter()
+            .filter(|student| {
+                student.name.to_lowercase().contains(&search)
+                    || student.email.to_lowercase().contains(&search)
+            })
+            .filter(|student| query.roll_min.is_none_or(|min| student.roll_number >= min))
+            .filter(|student| query.roll_max.is_none_or(|max| student.roll_number <= max))
+            .filter(|student| {
+                cursor.as_ref().is_none_or(|cursor| {
+                    cursor.is_followed_by(
+                        &query.sort.value_of(student),
+                        student.id.unwrap_or_default(),
+                        query.order,
+                    )
+                })
+            })
+            .cloned()
+            .collect();
+
+        students.sort_by(|a, b| {
+            let ordering = compare_bson(&query.sort.value_of(a), &query.sort.value_of(b))
+                .then_with(|| a.id.cmp(&b.id));
+            query.order.apply(ordering)
+        });
+        students.truncate(limit as usize + 1);
+
+        Ok(into_page(students, limit, |student| Cursor {
+            value: query.sort.value_of(student),
+            id: student.id.unwrap_or_default(),
+        }))
+    }
+}
@@ -0,0 +1,395 @@
+//! This module defines the `TeacherRepository` trait and its implementations.
+
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::models::page_model::{
+    Cursor, Page, compare_bson, escape_regex, find_page, into_page, page_limit,
+};
+use crate::models::teacher_model::{Teacher, TeacherListQuery};
+use crate::transaction::in_transaction;
+use async_trait::async_trait;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{Document, doc, oid::ObjectId},
+    options::{IndexOptions, ReturnDocument},
+};
+use tokio::sync::RwLock;
+use tracing::{error, info, instrument};
+
+/// Abstracts the storage of teachers, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait TeacherRepository: Send + Sync {
+    /// Inserts a new teacher and returns it with its generated ID. Fails with
+    /// `ErrorType::AlreadyExists` if another teacher has the same email.
+    async fn insert(&self, teacher: Teacher) -> Result<Teacher, ErrorType>;
+
+    /// Finds a teacher by their ID.
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Teacher>, ErrorType>;
+
+    /// Replaces the stored teacher having the same ID. Returns `false` if no such
+    /// teacher exists. Fails with `ErrorType::AlreadyExists` if another teacher has
+    /// the same email.
+    async fn update(&self, teacher: &Teacher) -> Result<bool, ErrorType>;
+
+    /// Assigns a class to a teacher, if it is not assigned already, and returns the
+    /// updated teacher. Returns `None` if no such teacher exists.
+    async fn assign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType>;
+
+    /// Removes a class from the classes of a teacher and returns the updated
+    /// teacher. Returns `None` if no such teacher exists.
+    async fn unassign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType>;
+
+    /// Removes a class from the classes of every teacher and returns how many
+    /// teachers were assigned it.
+    async fn unassign_class_from_all(&self, class_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Deletes a teacher by their ID. Returns `false` if no such teacher exists.
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType>;
+
+    /// Returns a page of the teachers matching the query.
+    async fn list(&self, query: &TeacherListQuery) -> Result<Page<Teacher>, ErrorType>;
+}
+
+/// A `TeacherRepository` backed by the MongoDB `teachers` collection.
+pub struct MongoTeacherRepository {
+    collection: Collection<Teacher>,
+}
+
+impl MongoTeacherRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `teachers` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the unique index on the email, which keeps two teachers from sharing
+    /// one even when they are added at the same time, and the index on the classes
+    /// for finding the teachers of a class.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let indexes = [
+            IndexModel::builder()
+                .keys(doc! { "email": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name("unique_email".to_string())
+                        .unique(true)
+                        .build(),
+                )
+                .build(),
+            IndexModel::builder().keys(doc! { "classes": 1 }).build(),
+        ];
+
+        self.collection
+            .create_indexes(indexes)
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error creating the teacher indexes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TeacherRepository for MongoTeacherRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut teacher: Teacher) -> Result<Teacher, ErrorType> {
+        teacher.id = None;
+
+        let insert_result = in_transaction!(self.collection.insert_one(&teacher))
+            .map_err(|err| map_write_error(err, "Error inserting teacher"))?;
+
+        teacher.id = insert_result.inserted_id.as_object_id();
+        Ok(teacher)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_id"))]
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Teacher>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "_id": id })).map_err(|err| {
+            error!(error = %err, "Error checking for teacher id");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, teacher: &Teacher) -> Result<bool, ErrorType> {
+        // The classes are left out, so that an assignment made at the same time is
+        // not overwritten.
+        let update_result = in_transaction!(self.collection.update_one(
+            doc! { "_id": teacher.id },
+            doc! { "$set": { "name": &teacher.name, "email": &teacher.email } },
+        ))
+        .map_err(|err| map_write_error(err, "Error updating teacher"))?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "assign_class"))]
+    async fn assign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType> {
+        in_transaction!(
+            self.collection
+                .find_one_and_update(
+                    doc! { "_id": id },
+                    doc! { "$addToSet": { "classes": class_id } },
+                )
+                .return_document(ReturnDocument::After)
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error assigning a class to teacher");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "unassign_class"))]
+    async fn unassign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType> {
+        in_transaction!(
+            self.collection
+                .find_one_and_update(
+                    doc! { "_id": id },
+                    doc! { "$pull": { "classes": class_id } },
+                )
+                .return_document(ReturnDocument::After)
+        )
+        .map_err(|err| {
+            error!(error = ?err, "Error unassigning a class from teacher");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "unassign_class_from_all"))]
+    async fn unassign_class_from_all(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        let update_result = in_transaction!(self.collection.update_many(
+            doc! { "classes": class_id },
+            doc! { "$pull": { "classes": class_id } },
+        ))
+        .map_err(|err| {
+            error!(error = ?err, "Error unassigning a class from the teachers");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+
+        Ok(update_result.modified_count)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "delete"))]
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let delete_result = in_transaction!(self.collection.delete_one(doc! { "_id": id }))
+            .map_err(|err| {
+                error!(error = ?err, "Error deleting teacher");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(delete_result.deleted_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "list"))]
+    async fn list(&self, query: &TeacherListQuery) -> Result<Page<Teacher>, ErrorType> {
+        let mut conditions: Vec<Document> = Vec::new();
+        if let Some(search) = query.search.as_deref().filter(|search| !search.is_empty()) {
+            let pattern = escape_regex(search);
+            conditions.push(doc! {
+                "$or": [
+                    { "name": { "$regex": &pattern, "$options": "i" } },
+                    { "email": { "$regex": &pattern, "$options": "i" } },
+                ]
+            });
+        }
+        if let Some(class_id) = query.class_id {
+            conditions.push(doc! { "classes": class_id });
+        }
+
+        find_page(
+            &self.collection,
+            conditions,
+            query.sort.field_name(),
+            query.order,
+            query.cursor.as_deref(),
+            query.limit,
+            |teacher| Cursor {
+                value: query.sort.value_of(teacher),
+                id: teacher.id.unwrap_or_default(),
+            },
+        )
+        .await
+    }
+}
+
+/// Maps a failed write to `ErrorType::AlreadyExists` when the unique index on the
+/// email rejected it, and to `ErrorType::ServerError` otherwise.
+fn map_write_error(err: mongodb::error::Error, message: &str) -> ErrorType {
+    if is_duplicate_key_error(&err) {
+        info!("Email already exists");
+        return email_taken();
+    }
+
+    error!(error = ?err, "{}", message);
+    ErrorType::ServerError("Server Error".to_string())
+}
+
+/// The error of a teacher whose email already belongs to another teacher.
+fn email_taken() -> ErrorType {
+    ErrorType::AlreadyExists("Teacher with email already exists".to_string())
+}
+
+/// A `TeacherRepository` that keeps teachers in memory. It is used to run the
+/// API without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryTeacherRepository {
+    teachers: RwLock<Vec<Teacher>>,
+}
+
+#[async_trait]
+impl TeacherRepository for InMemoryTeacherRepository {
+    async fn insert(&self, mut teacher: Teacher) -> Result<Teacher, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        if teachers.iter().any(|stored| stored.email == teacher.email) {
+            return Err(email_taken());
+        }
+
+        teacher.id = Some(ObjectId::new());
+        teachers.push(teacher.clone());
+        Ok(teacher)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<Teacher>, ErrorType> {
+        let teachers = self.teachers.read().await;
+        Ok(teachers
+            .iter()
+            .find(|teacher| teacher.id == Some(id))
+            .cloned())
+    }
+
+    async fn update(&self, teacher: &Teacher) -> Result<bool, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        if teachers
+            .iter()
+            .any(|stored| stored.id != teacher.id && stored.email == teacher.email)
+        {
+            return Err(email_taken());
+        }
+
+        match teachers.iter_mut().find(|stored| stored.id == teacher.id) {
+            Some(stored) => {
+                stored.name = teacher.name.clone();
+                stored.email = teacher.email.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn assign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        Ok(teachers
+            .iter_mut()
+            .find(|teacher| teacher.id == Some(id))
+            .map(|teacher| {
+                if !teacher.classes.contains(&class_id) {
+                    teacher.classes.push(class_id);
+                }
+                teacher.clone()
+            }))
+    }
+
+    async fn unassign_class(
+        &self,
+        id: ObjectId,
+        class_id: ObjectId,
+    ) -> Result<Option<Teacher>, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        Ok(teachers
+            .iter_mut()
+            .find(|teacher| teacher.id == Some(id))
+            .map(|teacher| {
+                teacher.classes.retain(|assigned| *assigned != class_id);
+                teacher.clone()
+            }))
+    }
+
+    async fn unassign_class_from_all(&self, class_id: ObjectId) -> Result<u64, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        let mut count = 0;
+        for teacher in teachers.iter_mut() {
+            if teacher.classes.contains(&class_id) {
+                teacher.classes.retain(|assigned| *assigned != class_id);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    async fn delete(&self, id: ObjectId) -> Result<bool, ErrorType> {
+        let mut teachers = self.teachers.write().await;
+        let len_before = teachers.len();
+        teachers.retain(|teacher| teacher.id != Some(id));
+        Ok(teachers.len() < len_before)
+    }
+
+    async fn list(&self, query: &TeacherListQuery) -> Result<Page<Teacher>, ErrorType> {
+        let limit = page_limit(query.limit);
+        let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+        let search = query.search.as_deref().unwrap_or_default().to_lowercase();
+
+        let mut teachers: Vec<Teacher> = self
+            .teachers
+            .read()
+            .await
+            .iter()
+            .filter(|teacher| {
+                teacher.name.to_lowercase().contains(&search)
+                    || teacher.email.to_lowercase().contains(&search)
+            })
+            .filter(|teacher| {
+                query
+                    .class_id
+                    .is_none_or(|class_id| teacher.classes.contains(&class_id))
+            })
+            .filter(|teacher| {
+                cursor.as_ref().is_none_or(|cursor| {
+                    cursor.is_followed_by(
+                        &query.sort.value_of(teacher),
+                        teacher.id.unwrap_or_default(),
+                        query.order,
+                    )
+                })
+            })
+            .cloned()
+            .collect();
+
+        teachers.sort_by(|a, b| {
+            let ordering = compare_bson(&query.sort.value_of(a), &query.sort.value_of(b))
+                .then_with(|| a.id.cmp(&b.id));
+            query.order.apply(ordering)
+        });
+        teachers.truncate(limit as usize + 1);
+
+        Ok(into_page(teachers, limit, |teacher| Cursor {
+            value: query.sort.value_of(teacher),
+            id: teacher.id.unwrap_or_default(),
+        }))
+    }
+}
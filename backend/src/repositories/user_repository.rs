@@ -0,0 +1,217 @@
+//! This module defines the `UserRepository` trait and its implementations.
+
+use crate::db::is_duplicate_key_error;
+use crate::error::ErrorType;
+use crate::models::user_model::User;
+use crate::transaction::in_transaction;
+use async_trait::async_trait;
+use mongodb::{
+    Collection, Database, IndexModel,
+    bson::{doc, oid::ObjectId},
+    options::IndexOptions,
+};
+use tokio::sync::RwLock;
+use tracing::{error, info, instrument};
+
+/// Abstracts the storage of user accounts, so that the routes do not depend on a
+/// particular database.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    /// Inserts a new user and returns it with its generated ID. Fails with
+    /// `ErrorType::AlreadyExists` if another user has the same email.
+    async fn insert(&self, user: User) -> Result<User, ErrorType>;
+
+    /// Finds a user by their ID.
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, ErrorType>;
+
+    /// Finds a user by their email.
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, ErrorType>;
+
+    /// Replaces the stored user having the same ID. Returns `false` if no such user
+    /// exists.
+    async fn update(&self, user: &User) -> Result<bool, ErrorType>;
+
+    /// Counts the users acting as a teacher.
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType>;
+
+    /// Counts the users acting as a student.
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType>;
+}
+
+/// A `UserRepository` backed by the MongoDB `users` collection.
+pub struct MongoUserRepository {
+    collection: Collection<User>,
+}
+
+impl MongoUserRepository {
+    /// Creates a new repository on the given collection of the given database,
+    /// `users` by default.
+    pub fn new(database: &Database, collection: &str) -> Self {
+        Self {
+            collection: database.collection(collection),
+        }
+    }
+
+    /// Creates the unique index on the email, which keeps two accounts from sharing
+    /// one even when they are added at the same time.
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "create_indexes"))]
+    pub async fn create_indexes(&self) -> Result<(), ErrorType> {
+        let unique_email = IndexModel::builder()
+            .keys(doc! { "email": 1 })
+            .options(
+                IndexOptions::builder()
+                    .name("unique_email".to_string())
+                    .unique(true)
+                    .build(),
+            )
+            .build();
+
+        self.collection
+            .create_index(unique_email)
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error creating the user indexes");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MongoUserRepository {
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "insert"))]
+    async fn insert(&self, mut user: User) -> Result<User, ErrorType> {
+        user.id = None;
+
+        let insert_result = in_transaction!(self.collection.insert_one(&user))
+            .map_err(|err| map_write_error(err, "Error inserting user"))?;
+
+        user.id = insert_result.inserted_id.as_object_id();
+        Ok(user)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_id"))]
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "_id": id })).map_err(|err| {
+            error!(error = %err, "Error checking for user id");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "find_by_email"))]
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, ErrorType> {
+        in_transaction!(self.collection.find_one(doc! { "email": email })).map_err(|err| {
+            error!(error = ?err, "Error checking for existing email");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "update"))]
+    async fn update(&self, user: &User) -> Result<bool, ErrorType> {
+        let update_result =
+            in_transaction!(self.collection.replace_one(doc! { "_id": user.id }, user))
+                .map_err(|err| map_write_error(err, "Error updating user"))?;
+
+        Ok(update_result.matched_count > 0)
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_teacher"))]
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "teacher_id": teacher_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the users of teacher");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+
+    #[instrument(name = "mongodb", skip_all, fields(collection = %self.collection.name(), operation = "count_by_student"))]
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        in_transaction!(
+            self.collection
+                .count_documents(doc! { "student_id": student_id })
+        )
+        .map_err(|err| {
+            error!(error = %err, "Error counting the users of student");
+            ErrorType::ServerError("Server Error".to_string())
+        })
+    }
+}
+
+/// Maps a failed write to `ErrorType::AlreadyExists` when the unique index on the
+/// email rejected it, and to `ErrorType::ServerError` otherwise.
+fn map_write_error(err: mongodb::error::Error, message: &str) -> ErrorType {
+    if is_duplicate_key_error(&err) {
+        info!("Email already exists");
+        return email_taken();
+    }
+
+    error!(error = ?err, "{}", message);
+    ErrorType::ServerError("Server Error".to_string())
+}
+
+/// The error of an account whose email already belongs to another account.
+fn email_taken() -> ErrorType {
+    ErrorType::AlreadyExists("User with email already exists".to_string())
+}
+
+/// A `UserRepository` that keeps users in memory. It is used to run the API
+/// without a MongoDB instance.
+#[derive(Default)]
+pub struct InMemoryUserRepository {
+    users: RwLock<Vec<User>>,
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn insert(&self, mut user: User) -> Result<User, ErrorType> {
+        let mut users = self.users.write().await;
+        if users.iter().any(|stored| stored.email == user.email) {
+            return Err(email_taken());
+        }
+
+        user.id = Some(ObjectId::new());
+        users.push(user.clone());
+        Ok(user)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> Result<Option<User>, ErrorType> {
+        let users = self.users.read().await;
+        Ok(users.iter().find(|user| user.id == Some(id)).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, ErrorType> {
+        let users = self.users.read().await;
+        Ok(users.iter().find(|user| user.email == email).cloned())
+    }
+
+    async fn update(&self, user: &User) -> Result<bool, ErrorType> {
+        let mut users = self.users.write().await;
+        match users.iter_mut().find(|stored| stored.id == user.id) {
+            Some(stored) => {
+                *stored = user.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn count_by_teacher(&self, teacher_id: ObjectId) -> Result<u64, ErrorType> {
+        let users = self.users.read().await;
+        Ok(users
+            .iter()
+            .filter(|user| user.teacher_id == Some(teacher_id))
+            .count() as u64)
+    }
+
+    async fn count_by_student(&self, student_id: ObjectId) -> Result<u64, ErrorType> {
+        let users = self.users.read().await;
+        Ok(users
+            .iter()
+            .filter(|user| user.student_id == Some(student_id))
+            .count() as u64)
+    }
+}
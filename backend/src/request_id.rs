@@ -0,0 +1,52 @@
+//! This module defines the request IDs. Every request gets a fresh ID, which is
+//! echoed in the `X-Request-Id` header of the response, written in the body of
+//! every error and recorded on the span of the request in the server logs, so that
+//! a client's report can be matched with the server output.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use mongodb::bson::oid::ObjectId;
+
+/// The ID of a request, stored in the extensions of the request so that the layers
+/// inside this one can read it.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// The header the request ID is returned in.
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    /// The ID of the request being handled by the current task.
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Returns the ID of the request being handled, or `None` outside of a request.
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware giving every request an ID. The rest of the request is handled with
+/// the ID in its extensions and available through `current_request_id`, and the
+/// response carries it in the `X-Request-Id` header.
+pub async fn request_id_layer(mut request: Request, next: Next) -> Response {
+    let request_id = ObjectId::new().to_hex();
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+
+    let mut response = CURRENT_REQUEST_ID
+        .scope(request_id.clone(), next.run(request))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER.clone(), value);
+    }
+
+    response
+}
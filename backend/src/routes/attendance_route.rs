@@ -1,22 +1,39 @@
 //! This module defines the routes for the attendance API.
 
-use crate::models::attendance_model::Attendance;
-use crate::models::student_model::Student;
+use crate::auth::AuthUser;
+use crate::error::ErrorType;
+use crate::extract::{Json, Path, Query};
+use crate::models::attendance_model::{
+    Attendance, AttendanceListQuery, AttendanceStatus, DuplicatePolicy, MarkAttendanceRequest,
+    RollCallRequest, RollCallResponse, RollCallResult,
+};
+use crate::models::page_model::Page;
+use crate::models::user_model::Role;
+use crate::routes::session_route::find_class_session;
 use crate::state::AppState;
-use crate::{error::ErrorType, models::class_model::Class};
-use axum::{Extension, Json, extract::Path};
+use crate::validation::Validate;
+use axum::Extension;
 use chrono::Utc;
-use mongodb::{Collection, bson::oid::ObjectId};
-use tokio_stream::StreamExt;
+use mongodb::bson::oid::ObjectId;
+use std::collections::{HashMap, HashSet};
 
 /// This function takes the application state and a JSON payload of attendance as input,
-/// and marks the attendance of the student. If the process is successful, the
-/// attendance details are returned.
+/// and marks the attendance of the student with the given status. The time is set
+/// by the server, and the late minutes are only kept for the `late` status. When a
+/// session is given, the class is taken from it and the enrollment is checked on
+/// the day of the session. A student is marked at most once per class and session,
+/// or per class and day without a session; a repeated mark fails unless
+/// `on_duplicate` is `return_existing`, in which case the existing record is
+/// returned. Teachers may only mark the classes assigned to them, and the record
+/// keeps the teacher who marked it. The checks and the insert run in a single
+/// transaction, so that a student deleted meanwhile is not left with a record. If
+/// the process is successful, the attendance details are returned.
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
-/// * `attendance_details` - The JSON payload of the attendance to add.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `request` - The JSON payload of the attendance to add.
 ///
 /// # Returns
 ///
@@ -26,178 +43,375 @@ use tokio_stream::StreamExt;
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
-/// * The class does not exist (`ErrorType::DoesNotExist`).
-/// * The student does not exist (`ErrorType::DoesNotExist`).
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * The student, the class or the session does not exist, neither a class nor a session is given, or the session belongs to another class (`ErrorType::Validation`).
+/// * The student is deleted while the attendance is marked (`ErrorType::DoesNotExist`).
+/// * The student is not enrolled in the class on that day (`ErrorType::NotEnrolled`).
+/// * The student is already marked and `on_duplicate` is `reject` (`ErrorType::AlreadyExists`).
 /// * There is an error marking the attendance (`ErrorType::ServerError`).
 pub async fn mark_attendance(
     Extension(state): Extension<AppState>,
-    mut attendance_details: Json<Attendance>,
+    user: AuthUser,
+    Json(mut request): Json<MarkAttendanceRequest>,
 ) -> Result<Json<Attendance>, ErrorType> {
-    let attendance_collection: Collection<Attendance> =
-        state.db_client.database("attendance").collection("records");
-
-    attendance_details.id = None;
-    attendance_details.time = Utc::now();
-    attendance_details.flag = true;
-    let mut new_details = attendance_details.0.clone();
-
-    // Check if student exists
-    let student_collection: Collection<Student> = state
-        .db_client
-        .database("attendance")
-        .collection("students");
-
-    let student_id = attendance_details.student_id;
-    let student_exist = student_collection
-        .find_one(mongodb::bson::doc! { "_id": &student_id })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing student: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
-
-    if student_exist.is_none() {
-        return Err(ErrorType::DoesNotExist(
-            "The student does not exist".to_string(),
-        ));
-    }
+    user.require(&[Role::Admin, Role::Teacher])?;
 
-    // Check if class exists
-    let class_collection: Collection<Class> =
-        state.db_client.database("attendance").collection("classes");
+    request.normalize();
 
-    let class_id = attendance_details.class_id.clone();
+    let mut new_details = Attendance {
+        id: None,
+        student_id: request.student_id,
+        class_id: request.class_id,
+        session_id: request.session_id,
+        date: None,
+        time: Utc::now(),
+        status: request.status,
+        reason: request.reason.clone(),
+        late_minutes: request
+            .late_minutes
+            .filter(|_| request.status == AttendanceStatus::Late),
+        marked_by: user.teacher_id,
+    };
 
-    let class_exist = class_collection
-        .find_one(mongodb::bson::doc! { "_id": &class_id })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing class: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
+    // The transaction may run several times, so it works on references and copies
+    // the new record on each run
+    let (state, user, request, details) = (&state, &user, &request, &new_details);
+    let result = state
+        .transactions
+        .run(|| async move {
+            request.validate(state).await?;
 
-    if class_exist.is_none() {
-        return Err(ErrorType::DoesNotExist(
-            "The class does not exist".to_string(),
-        ));
-    }
+            // Keep the student from being deleted until the record is written
+            if state.students.lock(&[request.student_id]).await? == 0 {
+                return Err(ErrorType::DoesNotExist(
+                    "The student does not exist".to_string(),
+                ));
+            }
 
-    match attendance_collection.insert_one(&new_details).await {
-        Ok(insert_result) => {
-            new_details.id = insert_result.inserted_id.as_object_id();
-            Ok(Json(new_details))
-        }
-        Err(err) => {
-            println!("Error inserting attendance: {:?}", err);
-            Err(ErrorType::ServerError("Server Error".to_string()))
+            let mut new_details = details.clone();
+            let mut attendance_date = new_details.time.date_naive();
+
+            // Take the class and the day from the session
+            if let Some(session_id) = new_details.session_id {
+                let Some(session) = state.sessions.find_by_id(session_id).await? else {
+                    return Err(ErrorType::DoesNotExist(
+                        "The session does not exist".to_string(),
+                    ));
+                };
+
+                new_details.class_id = Some(session.class_id);
+                attendance_date = session.start_time.date_naive();
+            }
+
+            let Some(class_id) = new_details.class_id else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+
+            user.require_class(state, class_id).await?;
+
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            // Check that the student is enrolled in the class on that day
+            let enrollment = state
+                .enrollments
+                .find_active(new_details.student_id, class_id, attendance_date)
+                .await?;
+
+            if enrollment.is_none() {
+                return Err(ErrorType::NotEnrolled(
+                    "The student is not enrolled in the class".to_string(),
+                ));
+            }
+
+            new_details.date = Some(attendance_date);
+            state.attendance.insert(new_details).await
+        })
+        .await;
+
+    match result {
+        // The failed insert aborted the transaction, so the existing record is read
+        // outside of it
+        Err(err @ ErrorType::AlreadyExists(_))
+            if request.on_duplicate == DuplicatePolicy::ReturnExisting =>
+        {
+            if let Some(session_id) = new_details.session_id {
+                let session = state.sessions.find_by_id(session_id).await?;
+                new_details.class_id = session.as_ref().map(|session| session.class_id);
+                new_details.date = session.map(|session| session.start_time.date_naive());
+            } else {
+                new_details.date = Some(new_details.time.date_naive());
+            }
+
+            // The existing record may have been deleted meanwhile, which still makes
+            // the mark a duplicate
+            match state.attendance.find_duplicate(&new_details).await? {
+                Some(existing) => Ok(Json(existing)),
+                None => Err(err),
+            }
         }
+        result => result.map(Json),
     }
 }
 
-/// This function takes the application state and a student ID as input,
-/// and searches the record for the attendance for a student in the database.
-/// If the record is found, the JSON payload of student's attendance
-/// is returned.
+/// This function takes the application state, a student ID and the listing query
+/// as input, and returns a page of the student's attendance records. The records
+/// can be filtered by a range of days, a status and a class, and sorted on the
+/// time they were marked or their ID. Teachers only see the records of the classes
+/// assigned to them. The `next_cursor` of the page is passed back as the `cursor`
+/// query parameter to get the following page.
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be the student, an admin or a teacher.
 /// * `student_id` - The ID of the student to search.
+/// * `query` - The query parameters of the listing.
 ///
 /// # Returns
 ///
-/// A `Result` containing a JSON payload of the student's attendance on success,
-/// or an `ErrorType` on failure.
+/// A `Result` containing a JSON payload of the page of the student's attendance on
+/// success, or an `ErrorType` on failure.
 ///
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
-/// * The student record is not found (`ErrorType::DoesNotExist`).
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or is neither the student, an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The range ends before it starts, or the cursor is not valid (`ErrorType::InvalidInput`).
 /// * There is an error searching the student's attendance in the database (`ErrorType::ServerError`).
 pub async fn get_attendance_by_student(
     Extension(state): Extension<AppState>,
+    user: AuthUser,
     Path(student_id): Path<ObjectId>,
-) -> Result<Json<Vec<Attendance>>, ErrorType> {
-    let collection: Collection<Attendance> =
-        state.db_client.database("attendance").collection("records");
-
-    let cursor_result = collection
-        .find(mongodb::bson::doc! {"student_id": student_id})
-        .await
-        .map_err(|err| {
-            println!("Error fetching the attendance: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        });
-
-    let mut attendances = Vec::new();
-
-    match cursor_result {
-        Ok(mut cursor) => {
-            while let Ok(Some(attendance)) = cursor.try_next().await.map_err(|err| {
-                println!("Error fetching the attendance: {}", err);
-                ErrorType::ServerError("Server Error".to_string())
-            }) {
-                attendances.push(attendance);
-            }
-        }
-        Err(_) => {
-            println!("Error fetching the attendances");
-        }
-    };
+    Query(mut query): Query<AttendanceListQuery>,
+) -> Result<Json<Page<Attendance>>, ErrorType> {
+    user.require_student_or(student_id, &[Role::Admin, Role::Teacher])?;
+
+    query.range().validate()?;
+    query.student_id = Some(student_id);
+    if user.role != Role::Student {
+        query.class_scope = user.assigned_classes(&state).await?;
+    }
+
+    let attendances = state.attendance.list(&query).await?;
 
     Ok(Json(attendances))
 }
 
-/// This function takes the application state and a class ID as input,
-/// and searches the record for the attendance for a class in the database.
-/// If the record is found, the JSON payload of class' attendance
-/// is returned.
+/// This function takes the application state, a class ID and the listing query as
+/// input, and returns a page of the class' attendance records. The records can be
+/// filtered by a range of days, a status and a student, and sorted on the time
+/// they were marked or their ID. The `next_cursor` of the page is passed back as
+/// the `cursor` query parameter to get the following page.
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
 /// * `class_id` - The ID of the class to search.
+/// * `query` - The query parameters of the listing.
 ///
 /// # Returns
 ///
-/// A `Result` containing a JSON payload of the class' attendance on success,
-/// or an `ErrorType` on failure.
+/// A `Result` containing a JSON payload of the page of the class' attendance on
+/// success, or an `ErrorType` on failure.
 ///
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
-/// * The class record is not found (`ErrorType::DoesNotExist`).
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The range ends before it starts, or the cursor is not valid (`ErrorType::InvalidInput`).
 /// * There is an error searching the class attendance in the database (`ErrorType::ServerError`).
 pub async fn get_attendance_by_class(
     Extension(state): Extension<AppState>,
+    user: AuthUser,
     Path(class_id): Path<ObjectId>,
-) -> Result<Json<Vec<Attendance>>, ErrorType> {
-    let collection: Collection<Attendance> =
-        state.db_client.database("attendance").collection("records");
-
-    let cursor_result = collection
-        .find(mongodb::bson::doc! {"class_id": class_id})
-        .await
-        .map_err(|err| {
-            println!("Error fetching the attendance: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        });
-
-    let mut attendances = Vec::new();
-
-    match cursor_result {
-        Ok(mut cursor) => {
-            while let Ok(Some(attendance)) = cursor.try_next().await.map_err(|err| {
-                println!("Error fetching the attendance: {}", err);
-                ErrorType::ServerError("Server Error".to_string())
-            }) {
-                attendances.push(attendance);
-            }
-        }
-        Err(_) => {
-            println!("Error fetching the attendances");
-        }
-    };
+    Query(mut query): Query<AttendanceListQuery>,
+) -> Result<Json<Page<Attendance>>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    query.range().validate()?;
+    query.class_id = Some(class_id);
+
+    let attendances = state.attendance.list(&query).await?;
 
     Ok(Json(attendances))
 }
+
+/// This function takes the application state, a class ID, a session ID and a JSON
+/// payload of the status of each student as input, and marks the attendance of
+/// the whole roster of the session at once. The students are validated against
+/// the roster of the class on the day of the session in a single query, and all
+/// the accepted records are written in a single insert. Students who are not
+/// enrolled, or whose ID is not valid, are reported in the results instead of
+/// failing the whole roll call. Students already marked for the session are
+/// reported as rejected, or given their existing record when `on_duplicate` is
+/// `return_existing`. Teachers may only call the roll of the classes assigned to
+/// them. The roster is read and the records are written in a single transaction.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `class_id` - The ID of the class the session belongs to.
+/// * `session_id` - The ID of the session for which attendance is marked.
+/// * `request` - The JSON payload of the status of each student.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the outcome for each student on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * The session is not found in the class, or a student is deleted while the roll is called (`ErrorType::DoesNotExist`).
+/// * There is an error marking the attendance (`ErrorType::ServerError`).
+pub async fn roll_call(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((class_id, session_id)): Path<(ObjectId, ObjectId)>,
+    Json(request): Json<RollCallRequest>,
+) -> Result<Json<RollCallResponse>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    // The transaction may run several times, so it works on references
+    let (state, user, request) = (&state, &user, &request);
+    let time = Utc::now();
+    let response = state
+        .transactions
+        .run(|| async move {
+            let session = find_class_session(state, class_id, session_id).await?;
+
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            // Fetch the roster of the session's day in one query
+            let roster: HashSet<ObjectId> = state
+                .enrollments
+                .find_active_in_class(class_id, session.start_time.date_naive())
+                .await?
+                .into_iter()
+                .map(|enrollment| enrollment.student_id)
+                .collect();
+
+            // Fetch the records already marked for the session in one query
+            let existing: HashMap<ObjectId, Attendance> = state
+                .attendance
+                .find_by_session(session_id)
+                .await?
+                .into_iter()
+                .map(|attendance| (attendance.student_id, attendance))
+                .collect();
+
+            let mut results = Vec::with_capacity(request.statuses.len());
+            let mut new_records = Vec::new();
+            let mut marked_students = Vec::new();
+
+            for (student_key, entry) in request.statuses.clone() {
+                let student_id = match ObjectId::parse_str(&student_key) {
+                    Ok(student_id) if roster.contains(&student_id) => student_id,
+                    Ok(_) => {
+                        results.push(RollCallResult::rejected(
+                            student_key,
+                            "The student is not enrolled in the class",
+                        ));
+                        continue;
+                    }
+                    Err(_) => {
+                        results.push(RollCallResult::rejected(
+                            student_key,
+                            "The student ID is not valid",
+                        ));
+                        continue;
+                    }
+                };
+
+                if let Some(attendance) = existing.get(&student_id) {
+                    results.push(match request.on_duplicate {
+                        DuplicatePolicy::Reject => RollCallResult::rejected(
+                            student_key,
+                            "The student is already marked for the session",
+                        ),
+                        DuplicatePolicy::ReturnExisting => RollCallResult {
+                            student_id: student_key,
+                            attendance: Some(attendance.clone()),
+                            error: None,
+                        },
+                    });
+                    continue;
+                }
+
+                let (status, reason, late_minutes) = entry.into_parts();
+                new_records.push(Attendance {
+                    id: None,
+                    student_id,
+                    class_id: Some(class_id),
+                    session_id: Some(session_id),
+                    date: Some(session.start_time.date_naive()),
+                    time,
+                    status,
+                    reason,
+                    late_minutes,
+                    marked_by: user.teacher_id,
+                });
+                marked_students.push(results.len());
+                results.push(RollCallResult {
+                    student_id: student_key,
+                    attendance: None,
+                    error: None,
+                });
+            }
+
+            // Keep the students from being deleted until their records are written, then
+            // write every accepted record in a single insert
+            let marked_ids: Vec<ObjectId> = new_records
+                .iter()
+                .map(|attendance| attendance.student_id)
+                .collect();
+            if state.students.lock(&marked_ids).await? != marked_ids.len() as u64 {
+                return Err(ErrorType::DoesNotExist(
+                    "A student of the roll call does not exist".to_string(),
+                ));
+            }
+            let inserted = state.attendance.insert_many(new_records).await?;
+
+            // Only the new records count as marked; the existing records returned for
+            // `return_existing` are neither marked nor rejected
+            let marked = inserted.len();
+            for (index, attendance) in marked_students.into_iter().zip(inserted) {
+                results[index].attendance = Some(attendance);
+            }
+            let rejected = results
+                .iter()
+                .filter(|result| result.error.is_some())
+                .count();
+
+            Ok(RollCallResponse {
+                marked,
+                rejected,
+                results,
+            })
+        })
+        .await?;
+
+    Ok(Json(response))
+}
@@ -0,0 +1,271 @@
+//! This module defines the routes for logging in, identifying the caller, and
+//! changing or resetting passwords.
+
+use crate::auth::{
+    AuthUser, RESET_TOKEN_LIFETIME_MINUTES, TOKEN_LIFETIME_SECONDS, generate_reset_token,
+    hash_password, hash_reset_token, verify_login, verify_password,
+};
+use crate::error::ErrorType;
+use crate::extract::Json;
+use crate::mail::Email;
+use crate::models::user_model::{
+    LoginRequest, LoginResponse, PasswordChangeRequest, PasswordReset, PasswordResetConfirm,
+    PasswordResetRequest, UserResponse,
+};
+use crate::state::AppState;
+use crate::validation::Validate;
+use axum::{Extension, http::StatusCode};
+use chrono::{Duration, Utc};
+use tracing::{Instrument, error};
+
+/// This function takes the application state and a JSON payload of credentials as
+/// input, and checks the password against the stored hash of the user. The email is
+/// trimmed like the one of a new account, and the password is checked against a
+/// dummy hash when no user has the email, so that a wrong email takes as long as a
+/// wrong password. If the credentials are valid, a signed JWT carrying the role of
+/// the user is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `request` - The JSON payload of the email and password.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the token on success, or an `ErrorType`
+/// on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The email or the password is wrong (`ErrorType::Unauthorized`).
+/// * There is an error searching the user or signing the token (`ErrorType::ServerError`).
+pub async fn login(
+    Extension(state): Extension<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, ErrorType> {
+    let account = state.users.find_by_email(request.email.trim()).await?;
+    let verified = verify_login(
+        &request.password,
+        account
+            .as_ref()
+            .map(|account| account.password_hash.as_str()),
+    )
+    .await;
+    let user = account
+        .filter(|_| verified)
+        .ok_or_else(|| ErrorType::Unauthorized("The email or password is wrong".to_string()))?;
+
+    let token = state.tokens.issue(&user)?;
+
+    Ok(Json(LoginResponse {
+        token,
+        token_type: "Bearer",
+        expires_in: TOKEN_LIFETIME_SECONDS,
+        role: user.role,
+    }))
+}
+
+/// This function takes the application state and the calling user as input, and
+/// returns the account the bearer token was issued for.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the caller's account on success, or an
+/// `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated, or their account no longer exists (`ErrorType::Unauthorized`).
+/// * There is an error searching the account in the database (`ErrorType::ServerError`).
+pub async fn get_me(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+) -> Result<Json<UserResponse>, ErrorType> {
+    let account = state
+        .users
+        .find_by_id(user.user_id)
+        .await?
+        .ok_or_else(|| ErrorType::Unauthorized("The account does not exist".to_string()))?;
+
+    Ok(Json(account.into()))
+}
+
+/// This function takes the application state, the calling user and a JSON payload
+/// of their current and new passwords as input, and replaces the password of the
+/// caller once the current one is checked.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route.
+/// * `request` - The JSON payload of the current and new passwords.
+///
+/// # Returns
+///
+/// A `Result` containing `204 No Content` on success, or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated, or the current password is wrong (`ErrorType::Unauthorized`).
+/// * The new password is empty (`ErrorType::Validation`).
+/// * There is an error updating the account in the database (`ErrorType::ServerError`).
+pub async fn change_password(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Json(request): Json<PasswordChangeRequest>,
+) -> Result<StatusCode, ErrorType> {
+    request.validate(&state).await?;
+
+    let wrong_password = || ErrorType::Unauthorized("The current password is wrong".to_string());
+    let mut account = state
+        .users
+        .find_by_id(user.user_id)
+        .await?
+        .ok_or_else(wrong_password)?;
+    if !verify_password(&request.current_password, &account.password_hash).await {
+        return Err(wrong_password());
+    }
+
+    account.set_password_hash(hash_password(&request.new_password).await?);
+    if !state.users.update(&account).await? {
+        return Err(ErrorType::Unauthorized(
+            "The account does not exist".to_string(),
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// This function takes the application state and a JSON payload of an email as
+/// input, and emails a single-use password-reset token to the account with that
+/// email. The account is looked up and the email sent by a background task, and the
+/// request is accepted right away, so that neither the response nor its timing
+/// reveals which emails have an account; a failure to find the account, store the
+/// token or send the email is only logged.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories and the mailer.
+/// * `request` - The JSON payload of the email of the account.
+///
+/// # Returns
+///
+/// `202 Accepted`, whether or not an account has the email.
+pub async fn request_password_reset(
+    Extension(state): Extension<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> StatusCode {
+    // The task keeps the span of the request, so its logs carry the request ID
+    tokio::spawn(
+        async move {
+            if let Err(err) = send_reset_token(&state, request.email.trim()).await {
+                error!(error = ?err, "Error sending the password reset");
+            }
+        }
+        .in_current_span(),
+    );
+
+    StatusCode::ACCEPTED
+}
+
+/// Stores a new password-reset token for the account with the given email, if there
+/// is one, and emails it to the account.
+async fn send_reset_token(state: &AppState, email: &str) -> Result<(), ErrorType> {
+    let Some(account) = state.users.find_by_email(email).await? else {
+        return Ok(());
+    };
+
+    let token = generate_reset_token();
+    state
+        .password_resets
+        .insert(PasswordReset {
+            id: None,
+            user_id: account.id.unwrap_or_default(),
+            token_hash: hash_reset_token(&token),
+            expires_at: Utc::now() + Duration::minutes(RESET_TOKEN_LIFETIME_MINUTES),
+            used_at: None,
+        })
+        .await?;
+
+    state
+        .mailer
+        .send(&Email {
+            to: account.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use this token to choose a new password within {} minutes:\n\n{}\n\n\
+                 If you did not ask for a new password, you can ignore this email.",
+                RESET_TOKEN_LIFETIME_MINUTES, token
+            ),
+        })
+        .await
+}
+
+/// This function takes the application state and a JSON payload of a reset token
+/// and a new password as input, and replaces the password of the account the token
+/// was issued for. A token can only be used once, and not after it expires. Once the
+/// password is replaced, every other token of the account is deleted as well, so
+/// that an earlier email can no longer reset it.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `request` - The JSON payload of the reset token and the new password.
+///
+/// # Returns
+///
+/// A `Result` containing `204 No Content` on success, or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The new password is empty (`ErrorType::Validation`).
+/// * The token is unknown, used or expired (`ErrorType::InvalidInput`).
+/// * There is an error updating the account in the database (`ErrorType::ServerError`).
+pub async fn reset_password(
+    Extension(state): Extension<AppState>,
+    Json(request): Json<PasswordResetConfirm>,
+) -> Result<StatusCode, ErrorType> {
+    request.validate(&state).await?;
+
+    let invalid = || ErrorType::InvalidInput("The reset token is not valid".to_string());
+    let token_hash = hash_reset_token(&request.token);
+    let password_hash = hash_password(&request.new_password).await?;
+
+    // The transaction may run several times, so it works on references
+    let (state, token_hash, password_hash) = (&state, &token_hash, &password_hash);
+    state
+        .transactions
+        .run(|| async move {
+            let reset = state
+                .password_resets
+                .consume(token_hash, Utc::now())
+                .await?
+                .ok_or_else(invalid)?;
+
+            let mut account = state
+                .users
+                .find_by_id(reset.user_id)
+                .await?
+                .ok_or_else(invalid)?;
+
+            account.set_password_hash(password_hash.clone());
+            if !state.users.update(&account).await? {
+                return Err(invalid());
+            }
+
+            state.password_resets.delete_by_user(reset.user_id).await?;
+            Ok(())
+        })
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
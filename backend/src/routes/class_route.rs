@@ -1,66 +1,89 @@
 //! This module defines the routes for the class API.
 
+use crate::auth::AuthUser;
 use crate::error::ErrorType;
-use crate::models::class_model::Class;
+use crate::extract::{Json, Path, Query};
+use crate::models::class_model::{Class, ClassListQuery, ClassUpdate};
+use crate::models::user_model::Role;
 use crate::state::AppState;
-use axum::{Extension, Json};
-use mongodb::Collection;
-use tokio_stream::StreamExt;
+use crate::validation::Validate;
+use axum::Extension;
+use mongodb::bson::oid::ObjectId;
 
-/// This function takes the application state and returns all the classes.
+/// This function takes the application state and the listing query as input, and
+/// returns the classes matching the query. Classes can be searched by a substring
+/// of their name or course code, and filtered by course code, department, term,
+/// credits, smallest capacity and the day of the week they meet on. Only the
+/// active classes are listed, unless the query asks for the archived ones.
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `_user` - The authenticated user calling the route, who may have any role.
+/// * `query` - The query parameters of the listing.
 ///
 /// # Returns
 ///
-/// A `Result` containing a JSON payload of all classes on success,
+/// A `Result` containing a JSON payload of the matching classes on success,
 /// or an `ErrorType` on failure.
 ///
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`).
 /// * There is an error fetching the classes from the database (`ErrorType::ServerError`).
 pub async fn get_classes(
     Extension(state): Extension<AppState>,
+    _user: AuthUser,
+    Query(query): Query<ClassListQuery>,
 ) -> Result<Json<Vec<Class>>, ErrorType> {
-    let class_collection: Collection<Class> =
-        state.db_client.database("attendance").collection("classes");
-
-    let cursor_result = class_collection
-        .find(mongodb::bson::doc! {})
-        .await
-        .map_err(|err| {
-            println!("Error fetching the classes: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        });
-
-    let mut classes = Vec::new();
-    match cursor_result {
-        Ok(mut cursor) => {
-            while let Ok(Some(class)) = cursor.try_next().await.map_err(|err| {
-                println!("Error fetching the classes: {}", err);
-                ErrorType::ServerError("Server Error".to_string())
-            }) {
-                classes.push(class);
-            }
-        }
-        Err(_) => {
-            println!("Error fetching the classes");
-        }
-    }
+    let classes = state.classes.list(&query).await?;
 
     Ok(Json(classes))
 }
 
+/// This function takes the application state and a class ID as input, and
+/// searches the class in the database. If the class is found, the JSON payload of
+/// the class is returned, whether it is archived or not.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `_user` - The authenticated user calling the route, who may have any role.
+/// * `class_id` - The ID of the class to search.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the searched class on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`).
+/// * The class is not found (`ErrorType::DoesNotExist`).
+/// * There is an error searching the class in the database (`ErrorType::ServerError`).
+pub async fn get_class(
+    Extension(state): Extension<AppState>,
+    _user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+) -> Result<Json<Class>, ErrorType> {
+    match state.classes.find_by_id(class_id).await? {
+        Some(class) => Ok(Json(class)),
+        None => Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
+        )),
+    }
+}
+
 /// This function takes the application state and a JSON payload of a class as input,
 /// and inserts the class into the database. If the insertion is successful, the
 /// newly inserted class with their ID is returned.
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
 /// * `class` - The JSON payload of the class to add.
 ///
 /// # Returns
@@ -71,41 +94,259 @@ pub async fn get_classes(
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The name is empty, or a metadata field or a schedule slot is not valid (`ErrorType::Validation`).
 /// * The class already exists in the database (`ErrorType::AlreadyExists`).
 /// * There is an error inserting the class into the database (`ErrorType::ServerError`).
 pub async fn add_class(
     Extension(state): Extension<AppState>,
-    mut class: Json<Class>,
+    user: AuthUser,
+    class: Json<Class>,
+) -> Result<Json<Class>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let mut new_class = class.0;
+    new_class.archived = false;
+
+    new_class.normalize();
+    new_class.validate(&state).await?;
+
+    // The unique index on the name rejects a class whose name is taken.
+    let new_class = state.classes.insert(new_class).await?;
+    Ok(Json(new_class))
+}
+
+/// This function takes the application state, a class ID and a JSON payload of the
+/// fields to change as input, and updates the class in the database, such as to
+/// rename it. The validation and name-uniqueness checks of `add_class` are run
+/// again on the updated class. If the update is successful, the updated class is
+/// returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to update.
+/// * `update` - The JSON payload of the fields to change.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated class on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class is not found (`ErrorType::DoesNotExist`).
+/// * The name is empty, or a metadata field or a schedule slot is not valid (`ErrorType::Validation`).
+/// * The name already belongs to another class (`ErrorType::AlreadyExists`).
+/// * There is an error updating the class in the database (`ErrorType::ServerError`).
+pub async fn update_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+    Json(update): Json<ClassUpdate>,
 ) -> Result<Json<Class>, ErrorType> {
-    let class_collection: Collection<Class> =
-        state.db_client.database("attendance").collection("classes");
-
-    class.id = None;
-
-    // Check if class already exists
-    let class_exist = class_collection
-        .find_one(mongodb::bson::doc! { "name": &class.name })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing class: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
-
-    if class_exist.is_some() {
-        return Err(ErrorType::AlreadyExists(
-            "The class already exists".to_string(),
+    user.require(&[Role::Admin])?;
+
+    let Some(mut class) = state.classes.find_by_id(class_id).await? else {
+        return Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
+        ));
+    };
+
+    if let Some(name) = update.name {
+        class.name = name;
+    }
+    if let Some(course_code) = update.course_code {
+        class.course_code = course_code;
+    }
+    if let Some(department) = update.department {
+        class.department = department;
+    }
+    if let Some(term) = update.term {
+        class.term = term;
+    }
+    if let Some(credits) = update.credits {
+        class.credits = credits;
+    }
+    if let Some(capacity) = update.capacity {
+        class.capacity = capacity;
+    }
+    if let Some(schedule) = update.schedule {
+        class.schedule = schedule;
+    }
+
+    class.normalize();
+    class.validate(&state).await?;
+
+    if !state.classes.update(&class).await? {
+        return Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
         ));
     }
 
-    match class_collection.insert_one(&*class).await {
-        Ok(insert_result) => {
-            class.id = insert_result.inserted_id.as_object_id();
-            let new_class = class.0.clone();
-            Ok(Json(new_class))
-        }
-        Err(err) => {
-            println!("Error inserting class: {:?}", err);
-            Err(ErrorType::ServerError("Server Error".to_string()))
+    Ok(Json(class))
+}
+
+/// This function takes the application state and a class ID as input, and archives
+/// the class. An archived class keeps its enrollments, sessions and attendance
+/// records, but takes no new enrollment, session or teacher, and is left out of the
+/// class listing by default. Archiving an archived class changes nothing. If the
+/// archiving is successful, the archived class is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to archive.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the archived class on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class is not found (`ErrorType::DoesNotExist`).
+/// * There is an error updating the class in the database (`ErrorType::ServerError`).
+pub async fn archive_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+) -> Result<Json<Class>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let class = set_archived(&state, class_id, true).await?;
+    Ok(Json(class))
+}
+
+/// This function takes the application state and a class ID as input, and restores
+/// an archived class, which takes new enrollments, sessions and teachers again.
+/// Unarchiving an active class changes nothing. If the restoring is successful, the
+/// restored class is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to restore.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the restored class on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class is not found (`ErrorType::DoesNotExist`).
+/// * There is an error updating the class in the database (`ErrorType::ServerError`).
+pub async fn unarchive_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+) -> Result<Json<Class>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let class = set_archived(&state, class_id, false).await?;
+    Ok(Json(class))
+}
+
+/// This function takes the application state and a class ID as input, and deletes
+/// the class from the database. A class that still has enrollments, sessions or
+/// attendance records is not deleted, so that none of them is left pointing to a
+/// deleted class; such a class can be archived instead. The class is removed from
+/// the classes of its teachers. The checks, the unassignment and the deletion run
+/// in a single transaction. If the deletion is successful, the deleted class is
+/// returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to delete.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the deleted class on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class is not found (`ErrorType::DoesNotExist`).
+/// * The class has enrollments, sessions or attendance records (`ErrorType::InUse`).
+/// * There is an error deleting the class from the database (`ErrorType::ServerError`).
+pub async fn delete_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+) -> Result<Json<Class>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    // The transaction may run several times, so it works on references
+    let state = &state;
+    let class = state
+        .transactions
+        .run(|| async move {
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+
+            if state.enrollments.count_by_class(class_id).await? > 0 {
+                return Err(ErrorType::InUse("The class has enrollments".to_string()));
+            }
+            if state.sessions.count_by_class(class_id).await? > 0 {
+                return Err(ErrorType::InUse("The class has sessions".to_string()));
+            }
+            if state.attendance.count_by_class(class_id).await? > 0 {
+                return Err(ErrorType::InUse(
+                    "The class has attendance records".to_string(),
+                ));
+            }
+
+            state.teachers.unassign_class_from_all(class_id).await?;
+            if !state.classes.delete(class_id).await? {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            }
+
+            Ok(class)
+        })
+        .await?;
+
+    Ok(Json(class))
+}
+
+/// Archives or restores a class and returns it.
+async fn set_archived(
+    state: &AppState,
+    class_id: ObjectId,
+    archived: bool,
+) -> Result<Class, ErrorType> {
+    let Some(mut class) = state.classes.find_by_id(class_id).await? else {
+        return Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
+        ));
+    };
+
+    if class.archived != archived {
+        class.archived = archived;
+        if !state.classes.update(&class).await? {
+            return Err(ErrorType::DoesNotExist(
+                "The class does not exist".to_string(),
+            ));
         }
     }
+
+    Ok(class)
 }
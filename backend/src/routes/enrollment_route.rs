@@ -0,0 +1,223 @@
+//! This module defines the routes for the enrollment API.
+
+use crate::auth::AuthUser;
+use crate::error::ErrorType;
+use crate::extract::{Json, Path, Query};
+use crate::models::enrollment_model::{EnrollRequest, Enrollment, RosterQuery, UnenrollRequest};
+use crate::models::user_model::Role;
+use crate::state::AppState;
+use crate::validation::Validate;
+use axum::Extension;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+
+/// This function takes the application state, a class ID and a JSON payload of the
+/// enrollment as input, and enrolls the student in the class. The checks and the
+/// insert run in a single transaction, so that a student deleted meanwhile is not
+/// left with an enrollment. If the enrollment is successful, the newly inserted
+/// enrollment with its ID is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to enroll the student in.
+/// * `request` - The JSON payload of the student and the enrollment dates.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the newly inserted enrollment on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class does not exist (`ErrorType::DoesNotExist`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * The student does not exist or the end date is before the start date (`ErrorType::Validation`).
+/// * The student is deleted while they are enrolled (`ErrorType::DoesNotExist`).
+/// * The student is already enrolled in the class over those dates (`ErrorType::AlreadyExists`).
+/// * There is an error inserting the enrollment into the database (`ErrorType::ServerError`).
+pub async fn enroll_student(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+    Json(request): Json<EnrollRequest>,
+) -> Result<Json<Enrollment>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let start_date = request
+        .start_date
+        .unwrap_or_else(|| Utc::now().date_naive());
+
+    // The transaction may run several times, so it works on references
+    let (state, request) = (&state, &request);
+    let enrollment = state
+        .transactions
+        .run(|| async move {
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            request.validate(state).await?;
+
+            // Keep the student from being deleted until the enrollment is written
+            if state.students.lock(&[request.student_id]).await? == 0 {
+                return Err(ErrorType::DoesNotExist(
+                    "The student does not exist".to_string(),
+                ));
+            }
+
+            // Check that the student is not already enrolled over those dates
+            let enrollments = state
+                .enrollments
+                .find_by_student_and_class(request.student_id, class_id)
+                .await?;
+
+            if enrollments
+                .iter()
+                .any(|enrollment| enrollment.overlaps(start_date, request.end_date))
+            {
+                return Err(ErrorType::AlreadyExists(
+                    "The student is already enrolled in the class".to_string(),
+                ));
+            }
+
+            let enrollment = Enrollment {
+                id: None,
+                student_id: request.student_id,
+                class_id,
+                start_date,
+                end_date: request.end_date,
+            };
+
+            state.enrollments.insert(enrollment).await
+        })
+        .await?;
+
+    Ok(Json(enrollment))
+}
+
+/// This function takes the application state, a class ID and a JSON payload naming
+/// the student as input, and ends the student's current enrollment in the class on
+/// the given end date. The enrollment is kept so that past attendance stays valid,
+/// unless it had not started yet, in which case it is removed. The lookup and the
+/// write run in a single transaction. If the process is successful, the ended
+/// enrollment is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `class_id` - The ID of the class to unenroll the student from.
+/// * `request` - The JSON payload of the student and the last day of the enrollment.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the ended enrollment on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The student is not enrolled in the class on the end date (`ErrorType::NotEnrolled`).
+/// * There is an error updating the enrollment in the database (`ErrorType::ServerError`).
+pub async fn unenroll_student(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+    Json(request): Json<UnenrollRequest>,
+) -> Result<Json<Enrollment>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let end_date = request.end_date.unwrap_or_else(|| Utc::now().date_naive());
+
+    // The transaction may run several times, so it works on references
+    let (state, request) = (&state, &request);
+    let enrollment = state
+        .transactions
+        .run(|| async move {
+            let enrollments = state
+                .enrollments
+                .find_by_student_and_class(request.student_id, class_id)
+                .await?;
+
+            // The enrollment either covers the end date or has not started by then
+            let Some(mut enrollment) = enrollments
+                .into_iter()
+                .filter(|enrollment| enrollment.overlaps(end_date, None))
+                .min_by_key(|enrollment| enrollment.start_date)
+            else {
+                return Err(ErrorType::NotEnrolled(
+                    "The student is not enrolled in the class".to_string(),
+                ));
+            };
+
+            let enrollment_id = enrollment.id.unwrap_or_default();
+
+            if end_date < enrollment.start_date {
+                state.enrollments.delete(enrollment_id).await?;
+                return Ok(enrollment);
+            }
+
+            enrollment.end_date = Some(end_date);
+            state.enrollments.update(&enrollment).await?;
+            Ok(enrollment)
+        })
+        .await?;
+
+    Ok(Json(enrollment))
+}
+
+/// This function takes the application state, a class ID and the roster query as
+/// input, and returns the enrollments of the class that are effective on the
+/// requested date. Teachers may only read the rosters of the classes assigned to
+/// them.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `class_id` - The ID of the class whose roster is listed.
+/// * `query` - The query parameters selecting the date of the roster.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the class' enrollments on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The class does not exist (`ErrorType::DoesNotExist`).
+/// * There is an error fetching the enrollments from the database (`ErrorType::ServerError`).
+pub async fn get_roster(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+    Query(query): Query<RosterQuery>,
+) -> Result<Json<Vec<Enrollment>>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    if state.classes.find_by_id(class_id).await?.is_none() {
+        return Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
+        ));
+    }
+
+    let date = query.date.unwrap_or_else(|| Utc::now().date_naive());
+    let roster = state
+        .enrollments
+        .find_active_in_class(class_id, date)
+        .await?;
+
+    Ok(Json(roster))
+}
@@ -0,0 +1,95 @@
+//! This module defines the routes of the health probes, which orchestrators call to
+//! know whether the server is running and whether it can serve requests.
+
+use crate::extract::Json;
+use crate::health::HealthCheck;
+use crate::models::health_model::{
+    DependencyHealth, DependencyStatus, LivenessResponse, ReadinessResponse, ServerStatus,
+};
+use crate::state::AppState;
+use axum::{Extension, http::StatusCode};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tokio::time::timeout;
+use tracing::warn;
+
+/// This function is the liveness probe. It answers as long as the server process
+/// is running, without checking any dependency.
+///
+/// # Returns
+///
+/// A JSON payload with the `alive` status.
+pub async fn get_liveness() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: ServerStatus::Alive,
+    })
+}
+
+/// This function takes the application state as input, and is the readiness probe.
+/// It checks every dependency of the server, such as the database, each within the
+/// configured health check timeout, and reports the status and latency of each one.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the health checks.
+///
+/// # Returns
+///
+/// `200 OK` with the `ready` status when every dependency is up, or
+/// `503 Service Unavailable` with the `not_ready` status otherwise, along with a
+/// JSON payload of the result of each dependency's check.
+pub async fn get_readiness(
+    Extension(state): Extension<AppState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let mut dependencies = BTreeMap::new();
+    let limit = state.config.server.health_check_timeout();
+    for health_check in &state.health_checks {
+        let health = check_dependency(health_check.as_ref(), limit).await;
+        dependencies.insert(health_check.name().to_string(), health);
+    }
+
+    let ready = dependencies
+        .values()
+        .all(|health| health.status == DependencyStatus::Up);
+    let (status_code, status) = if ready {
+        (StatusCode::OK, ServerStatus::Ready)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, ServerStatus::NotReady)
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            dependencies,
+        }),
+    )
+}
+
+/// Runs the health check of a dependency, timing it and giving up after the limit.
+async fn check_dependency(health_check: &dyn HealthCheck, limit: Duration) -> DependencyHealth {
+    let started = Instant::now();
+    let result = timeout(limit, health_check.check()).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let error = match result {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => Some(error),
+        Err(_) => Some(format!("No answer within {} ms", limit.as_millis())),
+    };
+    if let Some(error) = &error {
+        warn!(dependency = health_check.name(), %error, "Dependency is down");
+    }
+
+    DependencyHealth {
+        status: if error.is_none() {
+            DependencyStatus::Up
+        } else {
+            DependencyStatus::Down
+        },
+        latency_ms,
+        error,
+    }
+}
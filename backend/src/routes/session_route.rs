@@ -0,0 +1,311 @@
+//! This module defines the routes for the class session API.
+
+use crate::auth::AuthUser;
+use crate::error::ErrorType;
+use crate::extract::{Json, Path};
+use crate::models::session_model::{NewSession, Session, SessionUpdate};
+use crate::models::user_model::Role;
+use crate::state::AppState;
+use crate::validation::Validate;
+use axum::Extension;
+use mongodb::bson::oid::ObjectId;
+
+/// Finds a session of a class, failing if the session does not exist or belongs to
+/// another class.
+pub async fn find_class_session(
+    state: &AppState,
+    class_id: ObjectId,
+    session_id: ObjectId,
+) -> Result<Session, ErrorType> {
+    match state.sessions.find_by_id(session_id).await? {
+        Some(session) if session.class_id == class_id => Ok(session),
+        _ => Err(ErrorType::DoesNotExist(
+            "The session does not exist".to_string(),
+        )),
+    }
+}
+
+/// This function takes the application state, a class ID and a JSON payload of a
+/// session as input, and inserts the session of the class into the database. The
+/// checks and the insert run in a single transaction. Teachers may only add
+/// sessions to the classes assigned to them. If the insertion is successful, the
+/// newly inserted session with its ID is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `class_id` - The ID of the class the session belongs to.
+/// * `new_session` - The JSON payload of the session to add.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the newly inserted session on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The class does not exist (`ErrorType::DoesNotExist`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * The session does not end after it starts, the room is empty or the teacher does not exist (`ErrorType::Validation`).
+/// * There is an error inserting the session into the database (`ErrorType::ServerError`).
+pub async fn add_session(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+    Json(new_session): Json<NewSession>,
+) -> Result<Json<Session>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    let mut session = Session {
+        id: None,
+        class_id,
+        start_time: new_session.start_time,
+        end_time: new_session.end_time,
+        room: new_session.room,
+        teacher_id: new_session.teacher_id,
+    };
+    session.normalize();
+
+    // The transaction may run several times, so it works on references and copies
+    // the new session on each run
+    let (state, session) = (&state, &session);
+    let session = state
+        .transactions
+        .run(|| async move {
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            session.validate(state).await?;
+            state.sessions.insert(session.clone()).await
+        })
+        .await?;
+
+    Ok(Json(session))
+}
+
+/// This function takes the application state and a class ID as input, and returns
+/// all the sessions of the class in order of start time.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `_user` - The authenticated user calling the route, who may have any role.
+/// * `class_id` - The ID of the class whose sessions are listed.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the class' sessions on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`).
+/// * The class does not exist (`ErrorType::DoesNotExist`).
+/// * There is an error fetching the sessions from the database (`ErrorType::ServerError`).
+pub async fn get_sessions(
+    Extension(state): Extension<AppState>,
+    _user: AuthUser,
+    Path(class_id): Path<ObjectId>,
+) -> Result<Json<Vec<Session>>, ErrorType> {
+    if state.classes.find_by_id(class_id).await?.is_none() {
+        return Err(ErrorType::DoesNotExist(
+            "The class does not exist".to_string(),
+        ));
+    }
+
+    let sessions = state.sessions.find_by_class(class_id).await?;
+    Ok(Json(sessions))
+}
+
+/// This function takes the application state, a class ID and a session ID as input,
+/// and searches the session of the class in the database. If the session is found,
+/// the JSON payload of the session is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `_user` - The authenticated user calling the route, who may have any role.
+/// * `class_id` - The ID of the class the session belongs to.
+/// * `session_id` - The ID of the session to search.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the searched session on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`).
+/// * The session is not found in the class (`ErrorType::DoesNotExist`).
+/// * There is an error searching the session in the database (`ErrorType::ServerError`).
+pub async fn get_session(
+    Extension(state): Extension<AppState>,
+    _user: AuthUser,
+    Path((class_id, session_id)): Path<(ObjectId, ObjectId)>,
+) -> Result<Json<Session>, ErrorType> {
+    let session = find_class_session(&state, class_id, session_id).await?;
+    Ok(Json(session))
+}
+
+/// This function takes the application state, a class ID, a session ID and a JSON
+/// payload of the fields to change as input, and updates the session in the
+/// database. A session of an archived class cannot be updated, and a session that
+/// attendance has been marked for cannot move to another day, since its records
+/// are kept on the day of the session. The lookup, the checks and the update run
+/// in a single transaction. Teachers may only update the sessions of the classes
+/// assigned to them. If the update is successful, the updated session is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `class_id` - The ID of the class the session belongs to.
+/// * `session_id` - The ID of the session to update.
+/// * `update` - The JSON payload of the fields to change.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated session on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The session is not found in the class (`ErrorType::DoesNotExist`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * Attendance has been marked for the session and it moves to another day (`ErrorType::InUse`).
+/// * The session does not end after it starts, the room is empty or the teacher does not exist (`ErrorType::Validation`).
+/// * There is an error updating the session in the database (`ErrorType::ServerError`).
+pub async fn update_session(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((class_id, session_id)): Path<(ObjectId, ObjectId)>,
+    Json(update): Json<SessionUpdate>,
+) -> Result<Json<Session>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    // The transaction may run several times, so it works on references
+    let (state, update) = (&state, &update);
+    let session = state
+        .transactions
+        .run(|| async move {
+            let mut session = find_class_session(state, class_id, session_id).await?;
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            let day = session.start_time.date_naive();
+            if let Some(start_time) = update.start_time {
+                session.start_time = start_time;
+            }
+            if let Some(end_time) = update.end_time {
+                session.end_time = end_time;
+            }
+            if let Some(room) = &update.room {
+                session.room = Some(room.clone());
+            }
+            if let Some(teacher_id) = update.teacher_id {
+                session.teacher_id = Some(teacher_id);
+            }
+            session.normalize();
+            session.validate(state).await?;
+
+            // The records of the session are kept on its day
+            if session.start_time.date_naive() != day
+                && state.attendance.count_by_session(session_id).await? > 0
+            {
+                return Err(ErrorType::InUse(
+                    "Attendance has been marked for the session, so it cannot move to another day"
+                        .to_string(),
+                ));
+            }
+
+            if !state.sessions.update(&session).await? {
+                return Err(ErrorType::DoesNotExist(
+                    "The session does not exist".to_string(),
+                ));
+            }
+
+            Ok(session)
+        })
+        .await?;
+
+    Ok(Json(session))
+}
+
+/// This function takes the application state, a class ID and a session ID as input,
+/// and deletes the session from the database. A session that attendance has been
+/// marked for cannot be deleted. The checks and the deletion run in a single
+/// transaction. Teachers may only delete the sessions of the classes assigned to
+/// them. If the deletion is successful, the deleted session is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `class_id` - The ID of the class the session belongs to.
+/// * `session_id` - The ID of the session to delete.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the deleted session on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The class is not assigned to the calling teacher (`ErrorType::Forbidden`).
+/// * The session is not found in the class (`ErrorType::DoesNotExist`).
+/// * Attendance has been marked for the session (`ErrorType::InUse`).
+/// * There is an error deleting the session from the database (`ErrorType::ServerError`).
+pub async fn delete_session(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((class_id, session_id)): Path<(ObjectId, ObjectId)>,
+) -> Result<Json<Session>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+    user.require_class(&state, class_id).await?;
+
+    // The transaction may run several times, so it works on references
+    let state = &state;
+    let session = state
+        .transactions
+        .run(|| async move {
+            let session = find_class_session(state, class_id, session_id).await?;
+
+            if state.attendance.count_by_session(session_id).await? > 0 {
+                return Err(ErrorType::InUse(
+                    "Attendance has been marked for the session".to_string(),
+                ));
+            }
+
+            if !state.sessions.delete(session_id).await? {
+                return Err(ErrorType::DoesNotExist(
+                    "The session does not exist".to_string(),
+                ));
+            }
+
+            Ok(session)
+        })
+        .await?;
+
+    Ok(Json(session))
+}
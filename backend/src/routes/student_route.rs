@@ -1,10 +1,20 @@
 //! This module defines the routes for the student API.
 
+use crate::auth::AuthUser;
 use crate::error::ErrorType;
-use crate::models::student_model::Student;
+use crate::extract::{Json, Path, Query};
+use crate::models::attendance_model::{DateRange, StudentAttendanceSummary};
+use crate::models::page_model::Page;
+use crate::models::student_model::{
+    DeleteStudentQuery, RecordsPolicy, Student, StudentListQuery, StudentUpdate,
+};
+use crate::models::user_model::Role;
 use crate::state::AppState;
-use axum::{Extension, Json, extract::Path};
-use mongodb::{Collection, bson::oid::ObjectId};
+use crate::validation::Validate;
+use axum::Extension;
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use std::collections::BTreeMap;
 
 /// This function takes the application state and a JSON payload of a student as input,
 /// and inserts the student into the database. If the insertion is successful, the
@@ -12,7 +22,8 @@ use mongodb::{Collection, bson::oid::ObjectId};
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
 /// * `student` - The JSON payload of the student to add.
 ///
 /// # Returns
@@ -23,54 +34,28 @@ use mongodb::{Collection, bson::oid::ObjectId};
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
-/// * The roll number is not a positive number (`ErrorType::NegativeRollNumber`).
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The name is empty, the email is not valid or the roll number is not positive (`ErrorType::Validation`).
 /// * The email already exists in the database (`ErrorType::AlreadyExists`).
 /// * There is an error inserting the student into the database (`ErrorType::ServerError`).
 pub async fn add_student(
-    state: Extension<AppState>,
-    mut student: Json<Student>,
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    student: Json<Student>,
 ) -> Result<Json<Student>, ErrorType> {
-    let collection: Collection<Student> = state
-        .db_client
-        .database("attendance")
-        .collection("students");
+    user.require(&[Role::Admin])?;
 
     // We use `.0` here because `student` is of type `Json<Student>`, which is a tuple struct.
     // The actual `Student` value is stored in the first (and only) field of the `Json` wrapper,
     // so we access it with `.0`.
-    let mut new_student = student.0.clone();
-    student.id = None;
-
-    // Check that roll number is positive
-    if student.roll_number <= 0 {
-        println!("Roll number must be positive");
-        ErrorType::NegativeRollNumber("Roll number must be positive".to_string());
-    }
+    let mut new_student = student.0;
 
-    // Check that email does not already exist
-    let email_exists = collection
-        .find_one(mongodb::bson::doc! { "email": &student.email })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing email: {:?}", err);
-            ErrorType::ServerError("ServerError".to_string())
-        })?;
-
-    if email_exists.is_some() {
-        println!("Email already exists: {}", &student.email);
-        ErrorType::AlreadyExists("Student with email already exists".to_string());
-    }
+    new_student.normalize();
+    new_student.validate(&state).await?;
 
-    match collection.insert_one(&new_student).await {
-        Ok(insert_result) => {
-            new_student.id = insert_result.inserted_id.as_object_id();
-            Ok(Json(new_student))
-        }
-        Err(err) => {
-            println!("Error inserting student: {:?}", err);
-            Err(ErrorType::ServerError("Server Error".to_string()))
-        }
-    }
+    // The unique index on the email rejects a student whose email is taken.
+    let new_student = state.students.insert(new_student).await?;
+    Ok(Json(new_student))
 }
 
 /// This function takes the application state and a student ID as input,
@@ -79,7 +64,8 @@ pub async fn add_student(
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be the student, an admin or a teacher.
 /// * `student_id` - The ID of the student to search.
 ///
 /// # Returns
@@ -90,24 +76,17 @@ pub async fn add_student(
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or is neither the student, an admin nor a teacher (`ErrorType::Forbidden`).
 /// * The student record is not found (`ErrorType::DoesNotExist`).
 /// * There is an error searching the student in the database (`ErrorType::ServerError`).
 pub async fn get_student(
     Extension(state): Extension<AppState>,
+    user: AuthUser,
     Path(student_id): Path<ObjectId>,
 ) -> Result<Json<Student>, ErrorType> {
-    let collection = state
-        .db_client
-        .database("attendance")
-        .collection::<Student>("students");
-
-    let student_record = collection
-        .find_one(mongodb::bson::doc! { "_id": student_id })
-        .await
-        .map_err(|err| {
-            println!("Error checking for student id: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
+    user.require_student_or(student_id, &[Role::Admin, Role::Teacher])?;
+
+    let student_record = state.students.find_by_id(student_id).await?;
 
     match student_record {
         Some(record) => Ok(Json(record)),
@@ -116,3 +95,287 @@ pub async fn get_student(
         )),
     }
 }
+
+/// This function takes the application state, a student ID and a JSON payload of
+/// the fields to change as input, and updates the student in the database. The
+/// validation and email-uniqueness checks of `add_student` are run again on the
+/// updated student. If the update is successful, the updated student is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `student_id` - The ID of the student to update.
+/// * `update` - The JSON payload of the fields to change.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated student on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The student record is not found (`ErrorType::DoesNotExist`).
+/// * The name is empty, the email is not valid or the roll number is not positive (`ErrorType::Validation`).
+/// * The email already belongs to another student (`ErrorType::AlreadyExists`).
+/// * There is an error updating the student in the database (`ErrorType::ServerError`).
+pub async fn update_student(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(student_id): Path<ObjectId>,
+    Json(update): Json<StudentUpdate>,
+) -> Result<Json<Student>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let Some(mut student) = state.students.find_by_id(student_id).await? else {
+        return Err(ErrorType::DoesNotExist(
+            "The student record is not found".to_string(),
+        ));
+    };
+
+    if let Some(name) = update.name {
+        student.name = name;
+    }
+    if let Some(roll_number) = update.roll_number {
+        student.roll_number = roll_number;
+    }
+    if let Some(email) = update.email {
+        student.email = email;
+    }
+
+    student.normalize();
+    student.validate(&state).await?;
+
+    if !state.students.update(&student).await? {
+        return Err(ErrorType::DoesNotExist(
+            "The student record is not found".to_string(),
+        ));
+    }
+
+    Ok(Json(student))
+}
+
+/// This function takes the application state, a student ID and the deletion query
+/// as input, and deletes the student from the database. The student's attendance
+/// records are handled according to the `records` policy of the query: `block`
+/// refuses to delete a student who has records, `cascade` deletes them and
+/// `archive` moves them to the archive. The student's class enrollments are always
+/// removed. A student who still has a user account is not deleted, so that no
+/// account is left pointing to a deleted student. The records, the student and the enrollments are removed in a single
+/// transaction, so that a failure leaves none of them half deleted. If the
+/// deletion is successful, the deleted student is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `student_id` - The ID of the student to delete.
+/// * `query` - The query parameters selecting the records policy.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the deleted student on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The student record is not found (`ErrorType::DoesNotExist`).
+/// * A user account acts as the student, or the policy is `block` and the student has attendance records (`ErrorType::InUse`).
+/// * There is an error deleting the student from the database (`ErrorType::ServerError`).
+pub async fn delete_student(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(student_id): Path<ObjectId>,
+    Query(query): Query<DeleteStudentQuery>,
+) -> Result<Json<Student>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    // The transaction may run several times, so it works on references
+    let state = &state;
+    let student = state
+        .transactions
+        .run(|| async move {
+            let Some(student) = state.students.find_by_id(student_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The student record is not found".to_string(),
+                ));
+            };
+
+            if state.users.count_by_student(student_id).await? > 0 {
+                return Err(ErrorType::InUse(
+                    "The student has a user account".to_string(),
+                ));
+            }
+
+            match query.records {
+                RecordsPolicy::Block => {
+                    if state.attendance.count_by_student(student_id).await? > 0 {
+                        return Err(ErrorType::InUse(
+                            "The student has attendance records".to_string(),
+                        ));
+                    }
+                }
+                RecordsPolicy::Cascade => {
+                    state.attendance.delete_by_student(student_id).await?;
+                }
+                RecordsPolicy::Archive => {
+                    state.attendance.archive_by_student(student_id).await?;
+                }
+            }
+
+            if !state.students.delete(student_id).await? {
+                return Err(ErrorType::DoesNotExist(
+                    "The student record is not found".to_string(),
+                ));
+            }
+
+            state.enrollments.delete_by_student(student_id).await?;
+            Ok(student)
+        })
+        .await?;
+
+    Ok(Json(student))
+}
+
+/// This function takes the application state and the listing query as input, and
+/// returns a page of the students matching the query. Students can be searched by
+/// a substring of their name or email, filtered by a roll-number range and sorted
+/// on any of their fields. The `next_cursor` of the page is passed back as the
+/// `cursor` query parameter to get the following page.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `query` - The query parameters of the listing.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the page of students on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The cursor is not valid (`ErrorType::InvalidInput`).
+/// * There is an error fetching the students from the database (`ErrorType::ServerError`).
+pub async fn get_students(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Query(query): Query<StudentListQuery>,
+) -> Result<Json<Page<Student>>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+
+    let page = state.students.list(&query).await?;
+    Ok(Json(page))
+}
+
+/// This function takes the application state, a student ID and an optional date
+/// range as input, and summarizes the attendance of the student in each class:
+/// the sessions held, marked, attended, late and excused, and the attendance
+/// percentage. The sessions held are those the class held over the range, up to
+/// now, while the student was enrolled, so that a session the student was never
+/// marked for lowers the percentage. Late counts as attended; medical leave and
+/// official duty count as excused. Teachers only see the classes assigned to them.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be the student, an admin or a teacher.
+/// * `student_id` - The ID of the student to summarize.
+/// * `range` - The query parameters with the first and last day to include.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the student's summary on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or is neither the student, an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The student is not found (`ErrorType::DoesNotExist`).
+/// * The range ends before it starts (`ErrorType::InvalidInput`).
+/// * There is an error summarizing the attendance in the database (`ErrorType::ServerError`).
+pub async fn get_student_summary(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(student_id): Path<ObjectId>,
+    Query(range): Query<DateRange>,
+) -> Result<Json<StudentAttendanceSummary>, ErrorType> {
+    user.require_student_or(student_id, &[Role::Admin, Role::Teacher])?;
+
+    range.validate()?;
+
+    if state.students.find_by_id(student_id).await?.is_none() {
+        return Err(ErrorType::DoesNotExist(
+            "The student does not exist".to_string(),
+        ));
+    }
+
+    let sessions_held = sessions_held(&state, student_id, &range).await?;
+    let mut classes = state
+        .attendance
+        .summarize_student(student_id, &range, &sessions_held)
+        .await?;
+
+    // Teachers only see the classes assigned to them
+    if user.role != Role::Student
+        && let Some(assigned) = user.assigned_classes(&state).await?
+    {
+        classes.retain(|summary| {
+            summary
+                .class_id
+                .is_some_and(|class_id| assigned.contains(&class_id))
+        });
+    }
+
+    Ok(Json(StudentAttendanceSummary {
+        student_id,
+        from: range.from,
+        to: range.to,
+        classes,
+    }))
+}
+
+/// Counts the sessions each class of a student held over the range, up to now, on
+/// the days the student was enrolled in it.
+async fn sessions_held(
+    state: &AppState,
+    student_id: ObjectId,
+    range: &DateRange,
+) -> Result<BTreeMap<ObjectId, u64>, ErrorType> {
+    let enrollments = state.enrollments.find_by_student(student_id).await?;
+    let now = Utc::now();
+
+    let mut sessions_held = BTreeMap::new();
+    for enrollment in &enrollments {
+        if sessions_held.contains_key(&enrollment.class_id) {
+            continue;
+        }
+
+        let held = state
+            .sessions
+            .find_by_class(enrollment.class_id)
+            .await?
+            .iter()
+            .filter(|session| session.start_time <= now)
+            .map(|session| session.start_time.date_naive())
+            .filter(|&date| {
+                range.contains(date)
+                    && enrollments.iter().any(|enrollment_of_class| {
+                        enrollment_of_class.class_id == enrollment.class_id
+                            && enrollment_of_class.is_active_on(date)
+                    })
+            })
+            .count() as u64;
+        sessions_held.insert(enrollment.class_id, held);
+    }
+
+    Ok(sessions_held)
+}
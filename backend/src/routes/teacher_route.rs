@@ -1,10 +1,15 @@
 //! This module defines the routes for the teacher API.
 
+use crate::auth::AuthUser;
 use crate::error::ErrorType;
-use crate::models::{class_model::Class, teacher_model::Teacher};
+use crate::extract::{Json, Path, Query};
+use crate::models::page_model::Page;
+use crate::models::teacher_model::{Teacher, TeacherListQuery, TeacherUpdate};
+use crate::models::user_model::Role;
 use crate::state::AppState;
-use axum::{Extension, Json, extract::Path};
-use mongodb::{Collection, bson::oid::ObjectId};
+use crate::validation::Validate;
+use axum::Extension;
+use mongodb::bson::oid::ObjectId;
 
 /// This function takes the application state and a JSON payload of a teacher as input,
 /// and inserts the teacher into the database. If the insertion is successful, the
@@ -12,7 +17,8 @@ use mongodb::{Collection, bson::oid::ObjectId};
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
 /// * `teacher` - The JSON payload of the teacher to add.
 ///
 /// # Returns
@@ -23,65 +29,25 @@ use mongodb::{Collection, bson::oid::ObjectId};
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The name is empty, the email is not valid, or a class does not exist, is archived or is listed twice (`ErrorType::Validation`).
 /// * The email already exists in the database (`ErrorType::AlreadyExists`).
-/// * The class does not exist (`ErrorType::DoesNotExist`).
 /// * There is an error inserting the teacher into the database (`ErrorType::ServerError`).
 pub async fn add_teacher(
     Extension(state): Extension<AppState>,
-    mut teacher: Json<Teacher>,
+    user: AuthUser,
+    teacher: Json<Teacher>,
 ) -> Result<Json<Teacher>, ErrorType> {
-    let teacher_collection: Collection<Teacher> = state
-        .db_client
-        .database("attendance")
-        .collection("teachers");
-
-    let mut new_teacher = teacher.0.clone();
-    teacher.id = None;
-
-    // Check that email does not already exist
-    let email_exists = teacher_collection
-        .find_one(mongodb::bson::doc! { "email": &teacher.email })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing email: {:?}", err);
-            ErrorType::ServerError("ServerError".to_string())
-        })?;
-
-    if email_exists.is_some() {
-        println!("Email already exists: {}", &teacher.email);
-        ErrorType::AlreadyExists("Teacher with email already exists".to_string());
-    }
-
-    // Check if class exists
-    let class_collection: Collection<Class> =
-        state.db_client.database("attendance").collection("classes");
+    user.require(&[Role::Admin])?;
 
-    let class_id = teacher.class.clone();
+    let mut new_teacher = teacher.0;
 
-    let class_exist = class_collection
-        .find_one(mongodb::bson::doc! { "_id": &class_id })
-        .await
-        .map_err(|err| {
-            println!("Error checking for existing class: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
-
-    if class_exist.is_none() {
-        return Err(ErrorType::DoesNotExist(
-            "The class does not exist".to_string(),
-        ));
-    }
+    new_teacher.normalize();
+    new_teacher.validate(&state).await?;
 
-    match teacher_collection.insert_one(&new_teacher).await {
-        Ok(insert_result) => {
-            new_teacher.id = insert_result.inserted_id.as_object_id();
-            Ok(Json(new_teacher))
-        }
-        Err(err) => {
-            println!("Error inserting teacher: {:?}", err);
-            Err(ErrorType::ServerError("Server Error".to_string()))
-        }
-    }
+    // The unique index on the email rejects a teacher whose email is taken.
+    let new_teacher = state.teachers.insert(new_teacher).await?;
+    Ok(Json(new_teacher))
 }
 
 /// This function takes the application state and a teacher ID as input,
@@ -90,7 +56,8 @@ pub async fn add_teacher(
 ///
 /// # Arguments
 ///
-/// * `state` - The application state, which contains the database client.
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
 /// * `teacher_id` - The ID of the teacher to search.
 ///
 /// # Returns
@@ -101,24 +68,17 @@ pub async fn add_teacher(
 /// # Errors
 ///
 /// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
 /// * The teacher record is not found (`ErrorType::DoesNotExist`).
 /// * There is an error searching the teacher in the database (`ErrorType::ServerError`).
 pub async fn get_teacher(
     Extension(state): Extension<AppState>,
+    user: AuthUser,
     Path(teacher_id): Path<ObjectId>,
 ) -> Result<Json<Teacher>, ErrorType> {
-    let collection: Collection<Teacher> = state
-        .db_client
-        .database("attendance")
-        .collection("teachers");
-
-    let teacher_record = collection
-        .find_one(mongodb::bson::doc! { "_id": teacher_id })
-        .await
-        .map_err(|err| {
-            println!("Error checking for teacher id: {}", err);
-            ErrorType::ServerError("Server Error".to_string())
-        })?;
+    user.require(&[Role::Admin, Role::Teacher])?;
+
+    let teacher_record = state.teachers.find_by_id(teacher_id).await?;
 
     match teacher_record {
         Some(record) => Ok(Json(record)),
@@ -127,3 +87,266 @@ pub async fn get_teacher(
         )),
     }
 }
+
+/// This function takes the application state, a teacher ID and a JSON payload of
+/// the fields to change as input, and updates the teacher in the database. The
+/// changed fields are checked as in `add_teacher` and the email must stay unique;
+/// the classes are left as they are, even if one was archived since it was
+/// assigned. If the update is successful, the updated teacher is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `teacher_id` - The ID of the teacher to update.
+/// * `update` - The JSON payload of the fields to change.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated teacher on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The teacher record is not found (`ErrorType::DoesNotExist`).
+/// * The name is empty or the email is not valid (`ErrorType::Validation`).
+/// * The email already belongs to another teacher (`ErrorType::AlreadyExists`).
+/// * There is an error updating the teacher in the database (`ErrorType::ServerError`).
+pub async fn update_teacher(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(teacher_id): Path<ObjectId>,
+    Json(mut update): Json<TeacherUpdate>,
+) -> Result<Json<Teacher>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    update.normalize();
+    update.validate(&state).await?;
+
+    let Some(mut teacher) = state.teachers.find_by_id(teacher_id).await? else {
+        return Err(ErrorType::DoesNotExist(
+            "The teacher record is not found".to_string(),
+        ));
+    };
+
+    if let Some(name) = update.name {
+        teacher.name = name;
+    }
+    if let Some(email) = update.email {
+        teacher.email = email;
+    }
+
+    if !state.teachers.update(&teacher).await? {
+        return Err(ErrorType::DoesNotExist(
+            "The teacher record is not found".to_string(),
+        ));
+    }
+
+    Ok(Json(teacher))
+}
+
+/// This function takes the application state and a teacher ID as input, and
+/// deletes the teacher from the database. A teacher who still has a user account
+/// or takes sessions is not deleted, so that no account or session is left
+/// pointing to a deleted teacher; the attendance records they marked keep their
+/// reference. The checks and the deletion run in a single transaction. If the
+/// deletion is successful, the deleted teacher is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `teacher_id` - The ID of the teacher to delete.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the deleted teacher on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The teacher record is not found (`ErrorType::DoesNotExist`).
+/// * A user account acts as the teacher, or the teacher takes sessions (`ErrorType::InUse`).
+/// * There is an error deleting the teacher from the database (`ErrorType::ServerError`).
+pub async fn delete_teacher(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path(teacher_id): Path<ObjectId>,
+) -> Result<Json<Teacher>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    // The transaction may run several times, so it works on references
+    let state = &state;
+    let teacher = state
+        .transactions
+        .run(|| async move {
+            let Some(teacher) = state.teachers.find_by_id(teacher_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The teacher record is not found".to_string(),
+                ));
+            };
+
+            if state.users.count_by_teacher(teacher_id).await? > 0 {
+                return Err(ErrorType::InUse(
+                    "The teacher has a user account".to_string(),
+                ));
+            }
+            if state.sessions.count_by_teacher(teacher_id).await? > 0 {
+                return Err(ErrorType::InUse("The teacher takes sessions".to_string()));
+            }
+
+            if !state.teachers.delete(teacher_id).await? {
+                return Err(ErrorType::DoesNotExist(
+                    "The teacher record is not found".to_string(),
+                ));
+            }
+
+            Ok(teacher)
+        })
+        .await?;
+
+    Ok(Json(teacher))
+}
+
+/// This function takes the application state and the listing query as input, and
+/// returns a page of the teachers matching the query. Teachers can be searched by
+/// a substring of their name or email, filtered by an assigned class and sorted
+/// on their name or email. The `next_cursor` of the page is passed back as the
+/// `cursor` query parameter to get the following page.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin or a teacher.
+/// * `query` - The query parameters of the listing.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the page of teachers on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or neither an admin nor a teacher (`ErrorType::Forbidden`).
+/// * The cursor is not valid (`ErrorType::InvalidInput`).
+/// * There is an error fetching the teachers from the database (`ErrorType::ServerError`).
+pub async fn get_teachers(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Query(query): Query<TeacherListQuery>,
+) -> Result<Json<Page<Teacher>>, ErrorType> {
+    user.require(&[Role::Admin, Role::Teacher])?;
+
+    let page = state.teachers.list(&query).await?;
+    Ok(Json(page))
+}
+
+/// This function takes the application state, a teacher ID and a class ID as input,
+/// and assigns the class to the teacher. Assigning a class the teacher already
+/// takes changes nothing. The class check and the assignment run in a single
+/// transaction. If the assignment is successful, the updated teacher is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `teacher_id` - The ID of the teacher.
+/// * `class_id` - The ID of the class to assign.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated teacher on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The class or the teacher does not exist (`ErrorType::DoesNotExist`).
+/// * The class is archived (`ErrorType::Archived`).
+/// * There is an error updating the teacher in the database (`ErrorType::ServerError`).
+pub async fn assign_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((teacher_id, class_id)): Path<(ObjectId, ObjectId)>,
+) -> Result<Json<Teacher>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    // The transaction may run several times, so it works on references
+    let state = &state;
+    let teacher = state
+        .transactions
+        .run(|| async move {
+            let Some(class) = state.classes.find_by_id(class_id).await? else {
+                return Err(ErrorType::DoesNotExist(
+                    "The class does not exist".to_string(),
+                ));
+            };
+            class.require_active()?;
+
+            state
+                .teachers
+                .assign_class(teacher_id, class_id)
+                .await?
+                .ok_or_else(|| {
+                    ErrorType::DoesNotExist("The teacher record is not found".to_string())
+                })
+        })
+        .await?;
+
+    Ok(Json(teacher))
+}
+
+/// This function takes the application state, a teacher ID and a class ID as input,
+/// and removes the class from the classes of the teacher. If the removal is
+/// successful, the updated teacher is returned.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `teacher_id` - The ID of the teacher.
+/// * `class_id` - The ID of the class to unassign.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the updated teacher on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The teacher does not exist or the class is not assigned to them (`ErrorType::DoesNotExist`).
+/// * There is an error updating the teacher in the database (`ErrorType::ServerError`).
+pub async fn unassign_class(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Path((teacher_id, class_id)): Path<(ObjectId, ObjectId)>,
+) -> Result<Json<Teacher>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    let Some(teacher) = state.teachers.find_by_id(teacher_id).await? else {
+        return Err(ErrorType::DoesNotExist(
+            "The teacher record is not found".to_string(),
+        ));
+    };
+    if !teacher.classes.contains(&class_id) {
+        return Err(ErrorType::DoesNotExist(
+            "The class is not assigned to the teacher".to_string(),
+        ));
+    }
+
+    let teacher = state
+        .teachers
+        .unassign_class(teacher_id, class_id)
+        .await?
+        .ok_or_else(|| ErrorType::DoesNotExist("The teacher record is not found".to_string()))?;
+
+    Ok(Json(teacher))
+}
@@ -0,0 +1,72 @@
+//! This module defines the routes for the user account API.
+
+use crate::auth::{AuthUser, hash_password};
+use crate::error::ErrorType;
+use crate::extract::Json;
+use crate::models::user_model::{NewUser, Role, User, UserResponse};
+use crate::state::AppState;
+use crate::validation::Validate;
+use axum::Extension;
+
+/// This function takes the application state, the calling user and a JSON payload
+/// of an account as input, and creates the account with its password hashed. A
+/// `student` account must refer to an existing student and a `teacher` account to
+/// an existing teacher. If the creation is successful, the account is returned
+/// without its password hash.
+///
+/// # Arguments
+///
+/// * `state` - The application state, which contains the repositories.
+/// * `user` - The authenticated user calling the route, who must be an admin.
+/// * `new_user` - The JSON payload of the account to create.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON payload of the newly created account on success,
+/// or an `ErrorType` on failure.
+///
+/// # Errors
+///
+/// This function will return an `ErrorType` if:
+/// * The caller is not authenticated (`ErrorType::Unauthorized`) or not an admin (`ErrorType::Forbidden`).
+/// * The email is not valid, the password is empty, or the student or teacher of the role is missing or does not exist (`ErrorType::Validation`).
+/// * The email already belongs to an account (`ErrorType::AlreadyExists`).
+/// * There is an error creating the account (`ErrorType::ServerError`).
+pub async fn add_user(
+    Extension(state): Extension<AppState>,
+    user: AuthUser,
+    Json(mut new_user): Json<NewUser>,
+) -> Result<Json<UserResponse>, ErrorType> {
+    user.require(&[Role::Admin])?;
+
+    new_user.normalize();
+    new_user.validate(&state).await?;
+
+    // Only keep the reference matching the role
+    let (student_id, teacher_id) = match new_user.role {
+        Role::Admin => (None, None),
+        Role::Student => (new_user.student_id, None),
+        Role::Teacher => (None, new_user.teacher_id),
+    };
+
+    if state.users.find_by_email(&new_user.email).await?.is_some() {
+        return Err(ErrorType::AlreadyExists(
+            "User with email already exists".to_string(),
+        ));
+    }
+
+    let account = state
+        .users
+        .insert(User {
+            id: None,
+            email: new_user.email,
+            password_hash: hash_password(&new_user.password).await?,
+            role: new_user.role,
+            student_id,
+            teacher_id,
+            password_changed_at: None,
+        })
+        .await?;
+
+    Ok(Json(account.into()))
+}
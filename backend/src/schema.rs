@@ -0,0 +1,155 @@
+//! This module defines the `$jsonSchema` validators of the MongoDB collections. They
+//! are installed at startup, so that documents written outside of the API, such as
+//! by scripts or by hand, still have the shape the models expect.
+
+use crate::config::CollectionNames;
+use crate::error::ErrorType;
+use mongodb::{
+    Database,
+    bson::{Document, doc},
+    options::{ValidationAction, ValidationLevel},
+};
+use tracing::{error, info, instrument};
+
+/// Creates the collections that have a validator and do not exist yet, and installs
+/// the validators of the existing ones.
+///
+/// The validators use the `moderate` level: documents that were already invalid
+/// when the validator was installed can still be updated, but every insert and
+/// every update of a valid document is checked.
+#[instrument(name = "mongodb", skip_all, fields(operation = "install_validators"))]
+pub async fn install_validators(
+    database: &Database,
+    collections: &CollectionNames,
+) -> Result<(), ErrorType> {
+    let existing = database.list_collection_names().await.map_err(|err| {
+        error!(error = %err, "Error listing the collections");
+        ErrorType::ServerError("Server Error".to_string())
+    })?;
+
+    let validators = [
+        (&collections.students, student_schema()),
+        (&collections.teachers, teacher_schema()),
+        (&collections.classes, class_schema()),
+        (&collections.records, attendance_schema()),
+    ];
+
+    for (collection, schema) in validators {
+        let validator = doc! { "$jsonSchema": schema };
+        let result = if existing.contains(collection) {
+            database
+                .run_command(doc! {
+                    "collMod": collection.as_str(),
+                    "validator": validator,
+                    "validationLevel": "moderate",
+                    "validationAction": "error",
+                })
+                .await
+                .map(|_| ())
+        } else {
+            database
+                .create_collection(collection.as_str())
+                .validator(validator)
+                .validation_level(ValidationLevel::Moderate)
+                .validation_action(ValidationAction::Error)
+                .await
+        };
+
+        result.map_err(|err| {
+            error!(error = %err, collection = %collection, "Error installing the validator");
+            ErrorType::ServerError("Server Error".to_string())
+        })?;
+    }
+
+    info!("Installed the collection validators");
+    Ok(())
+}
+
+/// The schema of a student: a non-empty name, an email and a positive roll number.
+fn student_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["name", "email", "roll_number"],
+        "properties": {
+            "name": { "bsonType": "string", "minLength": 1 },
+            "email": { "bsonType": "string", "minLength": 1 },
+            "roll_number": { "bsonType": ["int", "long"], "minimum": 1 },
+        },
+    }
+}
+
+/// The schema of a teacher: a non-empty name, an email and the references of their
+/// classes, each listed once.
+fn teacher_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["name", "email", "classes"],
+        "properties": {
+            "name": { "bsonType": "string", "minLength": 1 },
+            "email": { "bsonType": "string", "minLength": 1 },
+            "classes": {
+                "bsonType": "array",
+                "items": { "bsonType": "objectId" },
+                "uniqueItems": true,
+            },
+        },
+    }
+}
+
+/// The schema of a class: a non-empty name, optional course metadata, and a default
+/// schedule whose slots have a day of the week and the times they start and end.
+/// The days of the week must match the serialization of `chrono::Weekday`.
+fn class_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["name"],
+        "properties": {
+            "name": { "bsonType": "string", "minLength": 1 },
+            "course_code": { "bsonType": ["string", "null"], "minLength": 1 },
+            "department": { "bsonType": ["string", "null"], "minLength": 1 },
+            "term": { "bsonType": ["string", "null"], "minLength": 1 },
+            "credits": { "bsonType": ["int", "long", "null"], "minimum": 0 },
+            "capacity": { "bsonType": ["int", "long", "null"], "minimum": 1 },
+            "schedule": {
+                "bsonType": "array",
+                "items": {
+                    "bsonType": "object",
+                    "required": ["weekday", "start_time", "end_time"],
+                    "properties": {
+                        "weekday": {
+                            "enum": ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+                        },
+                        "start_time": { "bsonType": "string" },
+                        "end_time": { "bsonType": "string" },
+                        "room": { "bsonType": ["string", "null"], "minLength": 1 },
+                    },
+                },
+            },
+            "archived": { "bsonType": "bool" },
+        },
+    }
+}
+
+/// The schema of an attendance record. The statuses must match
+/// `AttendanceStatus`. Legacy records, which have a `flag` instead of a `status`,
+/// are accepted as they are.
+fn attendance_schema() -> Document {
+    doc! {
+        "bsonType": "object",
+        "required": ["student_id", "time"],
+        "properties": {
+            "student_id": { "bsonType": "objectId" },
+            "class_id": { "bsonType": ["objectId", "null"] },
+            "session_id": { "bsonType": "objectId" },
+            "date": { "bsonType": "string" },
+            "time": { "bsonType": ["int", "long"] },
+            "status": {
+                "enum": ["present", "absent", "late", "excused", "medical_leave", "on_duty"],
+            },
+            "reason": { "bsonType": "string" },
+            "late_minutes": { "bsonType": ["int", "long"], "minimum": 0 },
+            "marked_by": { "bsonType": "objectId" },
+        },
+        "anyOf": [{ "required": ["status"] }, { "required": ["flag"] }],
+    }
+}
@@ -0,0 +1,70 @@
+//! This module defines the graceful shutdown of the server. On SIGINT or SIGTERM the
+//! server stops accepting connections and lets the in-flight requests finish, up to
+//! the configured deadline, before the database connections are closed.
+
+use tokio::{signal, sync::watch};
+use tracing::{error, info};
+
+/// A handle resolving once the shutdown has been requested. It can be cloned to be
+/// awaited by several parts of the application.
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Creates a shutdown handle and the sender requesting it.
+    pub fn channel() -> (watch::Sender<bool>, Self) {
+        let (sender, receiver) = watch::channel(false);
+        (sender, Self { receiver })
+    }
+
+    /// Creates a shutdown handle requested by the first SIGINT or SIGTERM received
+    /// by the process.
+    pub fn on_signal() -> Self {
+        let (sender, shutdown) = Self::channel();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            info!("Shutting down");
+            sender.send_replace(true);
+        });
+        shutdown
+    }
+
+    /// Waits until the shutdown is requested, or its sender is dropped.
+    pub async fn requested(&mut self) {
+        let _ = self.receiver.wait_for(|requested| *requested).await;
+    }
+}
+
+/// Waits for SIGINT (Ctrl+C) or, on Unix, SIGTERM. If a signal cannot be listened
+/// to, the error is logged and only the other one stops the server.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(err) = signal::ctrl_c().await {
+            error!(error = %err, "Error listening to SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                error!(error = %err, "Error listening to SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
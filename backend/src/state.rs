@@ -1,14 +1,138 @@
 //! This module defines the application state.
 
+use crate::auth::TokenKeys;
+use crate::config::AppConfig;
+use crate::error::ErrorType;
+use crate::health::{HealthCheck, MongoHealthCheck};
+use crate::mail::Mailer;
+use crate::repositories::{
+    attendance_repository::{
+        AttendanceRepository, InMemoryAttendanceRepository, MongoAttendanceRepository,
+    },
+    class_repository::{ClassRepository, InMemoryClassRepository, MongoClassRepository},
+    enrollment_repository::{
+        EnrollmentRepository, InMemoryEnrollmentRepository, MongoEnrollmentRepository,
+    },
+    password_reset_repository::{
+        InMemoryPasswordResetRepository, MongoPasswordResetRepository, PasswordResetRepository,
+    },
+    session_repository::{InMemorySessionRepository, MongoSessionRepository, SessionRepository},
+    student_repository::{InMemoryStudentRepository, MongoStudentRepository, StudentRepository},
+    teacher_repository::{InMemoryTeacherRepository, MongoTeacherRepository, TeacherRepository},
+    user_repository::{InMemoryUserRepository, MongoUserRepository, UserRepository},
+};
+use crate::schema;
+use crate::transaction::Transactions;
 use mongodb::Client;
 use std::sync::Arc;
 
 /// Represents the shared state of the application.
 ///
-/// This struct holds the database client, which is wrapped in an `Arc` to allow
-/// for safe sharing across multiple threads.
+/// This struct holds the configuration, the repositories used by the routes, the keys
+/// of the authentication tokens, the mailer, the health checks of the dependencies
+/// and the runner of the transactions, each wrapped in an `Arc` to allow for safe sharing across multiple threads.
 #[derive(Clone)]
 pub struct AppState {
-    /// The MongoDB client instance.
-    pub db_client: Arc<Client>
-}
\ No newline at end of file
+    /// The configuration the server was started with.
+    pub config: Arc<AppConfig>,
+    /// The repository of students.
+    pub students: Arc<dyn StudentRepository>,
+    /// The repository of teachers.
+    pub teachers: Arc<dyn TeacherRepository>,
+    /// The repository of classes.
+    pub classes: Arc<dyn ClassRepository>,
+    /// The repository of attendance records.
+    pub attendance: Arc<dyn AttendanceRepository>,
+    /// The repository of class enrollments.
+    pub enrollments: Arc<dyn EnrollmentRepository>,
+    /// The repository of class sessions.
+    pub sessions: Arc<dyn SessionRepository>,
+    /// The repository of user accounts.
+    pub users: Arc<dyn UserRepository>,
+    /// The repository of password-reset tokens.
+    pub password_resets: Arc<dyn PasswordResetRepository>,
+    /// The keys signing and verifying the authentication tokens.
+    pub tokens: Arc<TokenKeys>,
+    /// The transport of the emails sent to users.
+    pub mailer: Arc<dyn Mailer>,
+    /// The health checks of the dependencies, run by the readiness probe.
+    pub health_checks: Vec<Arc<dyn HealthCheck>>,
+    /// The runner of the multi-step writes, in transactions when the database
+    /// supports them.
+    pub transactions: Arc<Transactions>,
+}
+
+impl AppState {
+    /// Creates an application state whose repositories are backed by the configured
+    /// database and collections of the given MongoDB client, installing the
+    /// validators of the collections and creating the indexes the repositories rely
+    /// on.
+    pub async fn mongo(
+        client: &Client,
+        config: AppConfig,
+        tokens: TokenKeys,
+        mailer: Arc<dyn Mailer>,
+    ) -> Result<Self, ErrorType> {
+        let database = client.database(&config.database.name);
+        let collections = &config.database.collections;
+
+        schema::install_validators(&database, collections).await?;
+
+        let students = MongoStudentRepository::new(&database, &collections.students);
+        students.create_indexes().await?;
+        let teachers = MongoTeacherRepository::new(&database, &collections.teachers);
+        teachers.create_indexes().await?;
+        let classes = MongoClassRepository::new(&database, &collections.classes);
+        classes.create_indexes().await?;
+        let attendance = MongoAttendanceRepository::new(
+            &database,
+            &collections.records,
+            &collections.archived_records,
+        );
+        attendance.create_indexes().await?;
+        let enrollments = MongoEnrollmentRepository::new(&database, &collections.enrollments);
+        enrollments.create_indexes().await?;
+        let sessions = MongoSessionRepository::new(&database, &collections.sessions);
+        sessions.create_indexes().await?;
+        let users = MongoUserRepository::new(&database, &collections.users);
+        users.create_indexes().await?;
+        let password_resets =
+            MongoPasswordResetRepository::new(&database, &collections.password_resets);
+        password_resets.create_indexes().await?;
+
+        Ok(Self {
+            students: Arc::new(students),
+            teachers: Arc::new(teachers),
+            classes: Arc::new(classes),
+            attendance: Arc::new(attendance),
+            enrollments: Arc::new(enrollments),
+            sessions: Arc::new(sessions),
+            users: Arc::new(users),
+            password_resets: Arc::new(password_resets),
+            tokens: Arc::new(tokens),
+            mailer,
+            health_checks: vec![Arc::new(MongoHealthCheck::new(&database))],
+            transactions: Arc::new(Transactions::mongo(client).await?),
+            config: Arc::new(config),
+        })
+    }
+
+    /// Creates an application state whose repositories keep all data in memory.
+    pub fn in_memory(config: AppConfig, tokens: TokenKeys, mailer: Arc<dyn Mailer>) -> Self {
+        Self {
+            config: Arc::new(config),
+            students: Arc::new(InMemoryStudentRepository::default()),
+            teachers: Arc::new(InMemoryTeacherRepository::default()),
+            classes: Arc::new(InMemoryClassRepository::default()),
+            attendance: Arc::new(InMemoryAttendanceRepository::default()),
+            enrollments: Arc::new(InMemoryEnrollmentRepository::default()),
+            sessions: Arc::new(InMemorySessionRepository::default()),
+            users: Arc::new(InMemoryUserRepository::default()),
+            password_resets: Arc::new(InMemoryPasswordResetRepository::default()),
+            tokens: Arc::new(tokens),
+            mailer,
+            health_checks: Vec::new(),
+            transactions: Arc::new(Transactions::disabled()),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! This module defines the logging of the application. Logs are written with
+//! `tracing`, and every HTTP request gets a span carrying its method, path and
+//! request ID, so that every line logged while handling it can be traced back to it.
+
+use crate::request_id::RequestId;
+use axum::{extract::Request, http::Response};
+use serde::Deserialize;
+use std::{str::FromStr, time::Duration};
+use tracing::{Span, info, info_span};
+use tracing_subscriber::EnvFilter;
+
+/// The log filter used when `RUST_LOG` is not set.
+const DEFAULT_LOG_FILTER: &str = "info";
+
+/// How the log lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// One JSON object per line, for log collectors
+    Json,
+    /// Human-readable lines, for development
+    #[default]
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(LogFormat::Json),
+            "pretty" => Ok(LogFormat::Pretty),
+            _ => Err("expected `json` or `pretty`".to_string()),
+        }
+    }
+}
+
+/// Installs the global subscriber writing the logs in the given format. The levels
+/// are filtered by `RUST_LOG`, `info` by default.
+pub fn init_tracing(format: LogFormat) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Json => subscriber.json().flatten_event(true).init(),
+        LogFormat::Pretty => subscriber.pretty().init(),
+    }
+}
+
+/// Creates the span of an HTTP request, with its method, path and request ID.
+pub fn make_request_span(request: &Request) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|request_id| request_id.0.as_str())
+        .unwrap_or_default();
+
+    info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id = %request_id,
+    )
+}
+
+/// Logs the status and the latency of a response once it is ready.
+pub fn on_response<B>(response: &Response<B>, latency: Duration, _span: &Span) {
+    let status = response.status().as_u16();
+    let latency_ms = latency.as_millis() as u64;
+
+    info!(status, latency_ms, "Finished processing the request");
+}
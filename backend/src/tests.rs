@@ -0,0 +1,1378 @@
+//! This module tests the API through its router, against the in-memory storage. Each
+//! test builds its own application state, so the tests do not share any data.
+
+use crate::auth::{self, TokenKeys};
+use crate::config::AppConfig;
+use crate::error::ErrorType;
+use crate::mail::{Email, Mailer};
+use crate::router;
+use crate::state::AppState;
+use async_trait::async_trait;
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use chrono::Utc;
+use http_body_util::BodyExt;
+use mongodb::bson::oid::ObjectId;
+use serde_json::{Value, json};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{Mutex, mpsc};
+use tower::ServiceExt;
+
+/// The credentials of the admin account every test starts with.
+const ADMIN_EMAIL: &str = "admin@example.com";
+const ADMIN_PASSWORD: &str = "secret";
+
+/// A `Mailer` that hands the emails over to the test instead of sending them.
+struct ChannelMailer(mpsc::UnboundedSender<Email>);
+
+#[async_trait]
+impl Mailer for ChannelMailer {
+    async fn send(&self, email: &Email) -> Result<(), ErrorType> {
+        // The test may have ended and dropped the receiver
+        let _ = self.0.send(email.clone());
+        Ok(())
+    }
+}
+
+/// An application backed by the in-memory storage, with an admin logged in.
+struct TestApp {
+    router: Router,
+    admin: String,
+    emails: Mutex<mpsc::UnboundedReceiver<Email>>,
+}
+
+impl TestApp {
+    /// Creates the application and the admin account, and logs the admin in.
+    async fn new() -> Self {
+        let (sender, emails) = mpsc::unbounded_channel();
+        let state = AppState::in_memory(
+            AppConfig::default(),
+            TokenKeys::from_secret(b"test-secret"),
+            Arc::new(ChannelMailer(sender)),
+        );
+        auth::ensure_admin(&state, ADMIN_EMAIL, ADMIN_PASSWORD)
+            .await
+            .unwrap();
+
+        let mut app = Self {
+            router: router(state),
+            admin: String::new(),
+            emails: Mutex::new(emails),
+        };
+        app.admin = app.login(ADMIN_EMAIL, ADMIN_PASSWORD).await;
+        app
+    }
+
+    /// Waits for the next email the application sends.
+    async fn next_email(&self) -> Email {
+        tokio::time::timeout(Duration::from_secs(10), self.emails.lock().await.recv())
+            .await
+            .expect("no email was sent")
+            .unwrap()
+    }
+
+    /// Sends a request with an optional bearer token and JSON body, and returns the
+    /// status and the JSON body of the response, or `Value::Null` when it has none.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    /// Sends a request as the admin.
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.request(method, path, Some(&self.admin), body).await
+    }
+
+    /// Logs in and returns the token.
+    async fn login(&self, email: &str, password: &str) -> String {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/auth/login",
+                None,
+                Some(json!({ "email": email, "password": password })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["token"].as_str().unwrap().to_string()
+    }
+
+    /// Adds a student and returns its ID.
+    async fn add_student(&self, name: &str, roll_number: i32) -> String {
+        let (status, body) = self
+            .send(
+                Method::POST,
+                "/students/add",
+                Some(json!({
+                    "name": name,
+                    "roll_number": roll_number,
+                    "email": format!("{}@example.com", name.to_lowercase()),
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        id(&body)
+    }
+
+    /// Adds a class and returns its ID.
+    async fn add_class(&self, name: &str) -> String {
+        let (status, body) = self
+            .send(Method::POST, "/classes/add", Some(json!({ "name": name })))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        id(&body)
+    }
+
+    /// Enrolls a student in a class from today on.
+    async fn enroll(&self, class_id: &str, student_id: &str) {
+        let (status, body) = self
+            .send(
+                Method::POST,
+                &format!("/classes/{}/enroll", class_id),
+                Some(json!({ "student_id": student_id })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    /// Adds a one-hour session starting now to a class, and returns its ID.
+    async fn add_session(&self, class_id: &str) -> String {
+        let start_time = Utc::now().timestamp();
+        let (status, body) = self
+            .send(
+                Method::POST,
+                &format!("/classes/{}/sessions/add", class_id),
+                Some(json!({ "start_time": start_time, "end_time": start_time + 3600 })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        id(&body)
+    }
+
+    /// Marks a student present in a session, and returns the status and the body of
+    /// the response.
+    async fn mark_session(&self, student_id: &str, session_id: &str) -> (StatusCode, Value) {
+        self.send(
+            Method::POST,
+            "/attendance/mark",
+            Some(json!({ "student_id": student_id, "session_id": session_id })),
+        )
+        .await
+    }
+
+    /// Adds a teacher assigned to the given classes, with an account, and returns
+    /// the token of the teacher.
+    async fn add_teacher(&self, name: &str, classes: &[&str]) -> String {
+        let email = format!("{}@example.com", name.to_lowercase());
+        let (status, body) = self
+            .send(
+                Method::POST,
+                "/teachers/add",
+                Some(json!({ "name": name, "email": email, "classes": classes })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let (status, body) = self
+            .send(
+                Method::POST,
+                "/users/add",
+                Some(json!({
+                    "email": email,
+                    "password": "password",
+                    "role": "teacher",
+                    "teacher_id": id(&body),
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        self.login(&email, "password").await
+    }
+
+    /// Marks a student in a class with the default status, and returns the status and the body of
+    /// the response.
+    async fn mark(&self, student_id: &str, class_id: &str) -> (StatusCode, Value) {
+        self.send(
+            Method::POST,
+            "/attendance/mark",
+            Some(json!({
+                "student_id": student_id,
+                "class_id": class_id,
+            })),
+        )
+        .await
+    }
+}
+
+/// Returns the hex ID of a document returned by the API.
+fn id(document: &Value) -> String {
+    document["_id"]["$oid"].as_str().unwrap().to_string()
+}
+
+/// Returns the fields named by a validation error.
+fn invalid_fields(body: &Value) -> Vec<&str> {
+    body["details"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["field"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn reads_back_an_added_student() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+
+    let (status, body) = app
+        .send(Method::GET, &format!("/students/{}", student_id), None)
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Ada");
+    assert_eq!(body["roll_number"], 1);
+}
+
+#[tokio::test]
+async fn reports_a_missing_student() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app
+        .send(Method::GET, &format!("/students/{}", ObjectId::new()), None)
+        .await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+    assert!(body["request_id"].is_string(), "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_a_student_email_already_taken() {
+    let app = TestApp::new().await;
+    app.add_student("Ada", 1).await;
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/students/add",
+            Some(json!({ "name": "Ada", "roll_number": 2, "email": "ada@example.com" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn reports_every_invalid_field_of_a_student() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/students/add",
+            Some(json!({ "name": "  ", "roll_number": 0, "email": "not-an-email" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(
+        invalid_fields(&body),
+        ["name", "email", "roll_number"],
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn keeps_the_singular_teacher_routes() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/teacher/add",
+            Some(json!({ "name": "Grace", "email": "grace@example.com", "classes": [class_id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .send(Method::GET, &format!("/teacher/{}", id(&body)), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["email"], "grace@example.com");
+    assert_eq!(body["classes"][0]["$oid"], class_id.as_str());
+}
+
+#[tokio::test]
+async fn lists_the_attendance_of_a_student() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+
+    let (status, body) = app.mark(&student_id, &class_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/attendance/students/{}", student_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["items"].as_array().unwrap().len(), 1, "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_a_mark_for_a_missing_class() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+
+    let (status, _) = app.mark(&student_id, &ObjectId::new().to_hex()).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn updates_only_the_fields_given() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            &format!("/students/{}", student_id),
+            Some(json!({ "roll_number": 7 })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["name"], "Ada");
+    assert_eq!(body["roll_number"], 7);
+}
+
+#[tokio::test]
+async fn blocks_deleting_a_student_with_records() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    app.mark(&student_id, &class_id).await;
+
+    let (status, body) = app
+        .send(Method::DELETE, &format!("/students/{}", student_id), None)
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "in_use");
+}
+
+#[tokio::test]
+async fn deletes_the_records_of_a_student_on_cascade() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    app.mark(&student_id, &class_id).await;
+
+    let (status, body) = app
+        .send(
+            Method::DELETE,
+            &format!("/students/{}?records=cascade", student_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (_, body) = app
+        .send(
+            Method::GET,
+            &format!("/attendance/classes/{}", class_id),
+            None,
+        )
+        .await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 0, "{}", body);
+}
+
+#[tokio::test]
+async fn archives_the_records_of_a_student_when_asked() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    app.mark(&student_id, &class_id).await;
+
+    let (status, body) = app
+        .send(
+            Method::DELETE,
+            &format!("/students/{}?records=archive", student_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _) = app
+        .send(Method::GET, &format!("/students/{}", student_id), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = app
+        .send(
+            Method::GET,
+            &format!("/attendance/classes/{}", class_id),
+            None,
+        )
+        .await;
+    assert_eq!(body["items"].as_array().unwrap().len(), 0, "{}", body);
+}
+
+#[tokio::test]
+async fn blocks_deleting_a_student_with_an_account() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/users/add",
+            Some(json!({
+                "email": "ada@example.com",
+                "password": "password",
+                "role": "student",
+                "student_id": student_id,
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .send(
+            Method::DELETE,
+            &format!("/students/{}?records=cascade", student_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "in_use");
+}
+
+#[tokio::test]
+async fn pages_through_the_students_with_the_cursor() {
+    let app = TestApp::new().await;
+    for (name, roll_number) in [("Ada", 1), ("Brian", 2), ("Carol", 3)] {
+        app.add_student(name, roll_number).await;
+    }
+
+    let (status, first) = app
+        .send(Method::GET, "/students?sort=name&order=desc&limit=2", None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    let names: Vec<_> = first["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| &s["name"])
+        .collect();
+    assert_eq!(names, ["Carol", "Brian"]);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (status, second) = app
+        .send(
+            Method::GET,
+            &format!("/students?sort=name&order=desc&limit=2&cursor={}", cursor),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", second);
+    let names: Vec<_> = second["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| &s["name"])
+        .collect();
+    assert_eq!(names, ["Ada"]);
+    assert!(second["next_cursor"].is_null(), "{}", second);
+}
+
+#[tokio::test]
+async fn rejects_a_cursor_it_did_not_issue() {
+    let app = TestApp::new().await;
+
+    let (status, _) = app
+        .send(Method::GET, "/students?cursor=not-a-cursor", None)
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn rejects_a_mark_for_a_student_not_enrolled() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+
+    let (status, body) = app.mark(&student_id, &class_id).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "not_enrolled");
+}
+
+#[tokio::test]
+async fn keeps_the_late_minutes_only_for_late_marks() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/attendance/mark",
+            Some(json!({
+                "student_id": student_id,
+                "class_id": class_id,
+                "status": "excused",
+                "reason": "Doctor's appointment",
+                "late_minutes": 10,
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "excused");
+    assert_eq!(body["reason"], "Doctor's appointment");
+    assert!(body.get("late_minutes").is_none(), "{}", body);
+}
+
+#[tokio::test]
+async fn takes_the_class_of_a_mark_from_its_session() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    let session_id = app.add_session(&class_id).await;
+
+    let (status, body) = app.mark_session(&student_id, &session_id).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["class_id"]["$oid"], class_id.as_str());
+    assert_eq!(body["status"], "present");
+}
+
+#[tokio::test]
+async fn blocks_deleting_a_session_with_records() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    let session_id = app.add_session(&class_id).await;
+    app.mark_session(&student_id, &session_id).await;
+
+    let (status, _) = app
+        .send(
+            Method::DELETE,
+            &format!("/classes/{}/sessions/{}", class_id, session_id),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn reports_the_students_a_roll_call_rejects() {
+    let app = TestApp::new().await;
+    let enrolled = app.add_student("Ada", 1).await;
+    let outsider = app.add_student("Brian", 2).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &enrolled).await;
+    let session_id = app.add_session(&class_id).await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/classes/{}/sessions/{}/rollcall", class_id, session_id),
+            Some(json!({
+                "statuses": {
+                    &enrolled: { "status": "late", "late_minutes": 5 },
+                    &outsider: "present",
+                },
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["marked"], 1, "{}", body);
+    assert_eq!(body["rejected"], 1, "{}", body);
+}
+
+#[tokio::test]
+async fn counts_only_the_new_records_of_a_roll_call() {
+    let app = TestApp::new().await;
+    let ada = app.add_student("Ada", 1).await;
+    let brian = app.add_student("Brian", 2).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &ada).await;
+    app.enroll(&class_id, &brian).await;
+    let session_id = app.add_session(&class_id).await;
+    let (status, body) = app.mark_session(&ada, &session_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/classes/{}/sessions/{}/rollcall", class_id, session_id),
+            Some(json!({
+                "statuses": { &ada: "present", &brian: "present" },
+                "on_duplicate": "return_existing",
+            })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["marked"], 1, "{}", body);
+    assert_eq!(body["rejected"], 0, "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_a_repeated_mark_by_default() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+
+    let (status, body) = app.mark(&student_id, &class_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = app.mark(&student_id, &class_id).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "already_exists");
+}
+
+#[tokio::test]
+async fn returns_the_existing_mark_when_asked() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    let session_id = app.add_session(&class_id).await;
+
+    let (status, first) = app.mark_session(&student_id, &session_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+
+    let (status, second) = app
+        .send(
+            Method::POST,
+            "/attendance/mark",
+            Some(json!({
+                "student_id": student_id,
+                "session_id": session_id,
+                "status": "absent",
+                "on_duplicate": "return_existing",
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", second);
+    assert_eq!(id(&second), id(&first));
+    assert_eq!(second["status"], "present");
+}
+
+#[tokio::test]
+async fn summarizes_the_attendance_of_a_student() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    for status in ["late", "absent"] {
+        let session_id = app.add_session(&class_id).await;
+        let (code, body) = app
+            .send(
+                Method::POST,
+                "/attendance/mark",
+                Some(
+                    json!({ "student_id": student_id, "session_id": session_id, "status": status }),
+                ),
+            )
+            .await;
+        assert_eq!(code, StatusCode::OK, "{}", body);
+    }
+    // A session the student was never marked for still counts as held
+    app.add_session(&class_id).await;
+
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/students/{}/summary", student_id),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let class = &body["classes"][0];
+    assert_eq!(class["class_id"]["$oid"], class_id.as_str(), "{}", body);
+    assert_eq!(class["sessions_held"], 3, "{}", body);
+    assert_eq!(class["marked"], 2, "{}", body);
+    assert_eq!(class["attended"], 1, "{}", body);
+    assert_eq!(class["late"], 1, "{}", body);
+    assert_eq!(class["percentage"], 33.33, "{}", body);
+}
+
+#[tokio::test]
+async fn pages_through_the_filtered_records_of_a_class() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+    let session_id = app.add_session(&class_id).await;
+    for (name, roll_number, status) in [
+        ("Ada", 1, "present"),
+        ("Brian", 2, "absent"),
+        ("Carol", 3, "present"),
+    ] {
+        let student_id = app.add_student(name, roll_number).await;
+        app.enroll(&class_id, &student_id).await;
+        let (code, body) = app
+            .send(
+                Method::POST,
+                "/attendance/mark",
+                Some(
+                    json!({ "student_id": student_id, "session_id": session_id, "status": status }),
+                ),
+            )
+            .await;
+        assert_eq!(code, StatusCode::OK, "{}", body);
+    }
+    let path = format!("/attendance/classes/{}?status=present&limit=1", class_id);
+
+    let (status, first) = app.send(Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    assert_eq!(first["items"].as_array().unwrap().len(), 1, "{}", first);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (status, second) = app
+        .send(Method::GET, &format!("{}&cursor={}", path, cursor), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", second);
+    assert_eq!(second["items"].as_array().unwrap().len(), 1, "{}", second);
+    assert_ne!(id(&second["items"][0]), id(&first["items"][0]));
+    assert!(second["next_cursor"].is_null(), "{}", second);
+}
+
+#[tokio::test]
+async fn rejects_requests_without_a_token() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app.request(Method::GET, "/students", None, None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+}
+
+#[tokio::test]
+async fn forbids_teachers_from_adding_students() {
+    let app = TestApp::new().await;
+    let teacher = app.add_teacher("Grace", &[]).await;
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/students/add",
+            Some(&teacher),
+            Some(json!({ "name": "Ada", "roll_number": 1, "email": "ada@example.com" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "forbidden");
+}
+
+#[tokio::test]
+async fn lets_teachers_mark_only_their_classes() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let own_class = app.add_class("Algebra").await;
+    let other_class = app.add_class("Biology").await;
+    app.enroll(&own_class, &student_id).await;
+    app.enroll(&other_class, &student_id).await;
+    let teacher = app.add_teacher("Grace", &[&own_class]).await;
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/attendance/mark",
+            Some(&teacher),
+            Some(json!({ "student_id": student_id, "class_id": other_class })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/attendance/mark",
+            Some(&teacher),
+            Some(json!({ "student_id": student_id, "class_id": own_class })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["marked_by"].is_object(), "{}", body);
+}
+
+#[tokio::test]
+async fn forbids_teachers_from_the_records_of_other_classes() {
+    let app = TestApp::new().await;
+    let own_class = app.add_class("Algebra").await;
+    let other_class = app.add_class("Biology").await;
+    let teacher = app.add_teacher("Grace", &[&own_class]).await;
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/attendance/classes/{}", own_class),
+            Some(&teacher),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/attendance/classes/{}", other_class),
+            Some(&teacher),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn forbids_teachers_from_the_sessions_of_other_classes() {
+    let app = TestApp::new().await;
+    let own_class = app.add_class("Algebra").await;
+    let other_class = app.add_class("Biology").await;
+    let teacher = app.add_teacher("Grace", &[&own_class]).await;
+    let other_session = app.add_session(&other_class).await;
+    let start_time = Utc::now().timestamp();
+    let session = json!({ "start_time": start_time, "end_time": start_time + 3600 });
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            &format!("/classes/{}/sessions/add", own_class),
+            Some(&teacher),
+            Some(session.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            &format!("/classes/{}/sessions/add", other_class),
+            Some(&teacher),
+            Some(session),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app
+        .request(
+            Method::DELETE,
+            &format!("/classes/{}/sessions/{}", other_class, other_session),
+            Some(&teacher),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn forbids_teachers_from_other_rosters() {
+    let app = TestApp::new().await;
+    let own_class = app.add_class("Algebra").await;
+    let other_class = app.add_class("Biology").await;
+    let teacher = app.add_teacher("Grace", &[&own_class]).await;
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/classes/{}/roster", own_class),
+            Some(&teacher),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app
+        .request(
+            Method::GET,
+            &format!("/classes/{}/roster", other_class),
+            Some(&teacher),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn changes_the_password_of_the_caller() {
+    let app = TestApp::new().await;
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            "/auth/password",
+            Some(json!({ "current_password": "wrong", "new_password": "changed" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/auth/password",
+            Some(json!({ "current_password": ADMIN_PASSWORD, "new_password": "changed" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/auth/login",
+            None,
+            Some(json!({ "email": ADMIN_EMAIL, "password": ADMIN_PASSWORD })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    app.login(ADMIN_EMAIL, "changed").await;
+}
+
+#[tokio::test]
+async fn rejects_an_unknown_email_like_a_wrong_password() {
+    let app = TestApp::new().await;
+    app.login(&format!(" {} ", ADMIN_EMAIL), ADMIN_PASSWORD)
+        .await;
+
+    let (wrong_password, wrong_password_body) = app
+        .request(
+            Method::POST,
+            "/auth/login",
+            None,
+            Some(json!({ "email": ADMIN_EMAIL, "password": "wrong" })),
+        )
+        .await;
+    let (unknown_email, unknown_email_body) = app
+        .request(
+            Method::POST,
+            "/auth/login",
+            None,
+            Some(json!({ "email": "nobody@example.com", "password": "wrong" })),
+        )
+        .await;
+
+    assert_eq!(wrong_password, StatusCode::UNAUTHORIZED);
+    assert_eq!(unknown_email, wrong_password);
+    assert_eq!(
+        unknown_email_body["message"],
+        wrong_password_body["message"]
+    );
+}
+
+#[tokio::test]
+async fn rejects_the_tokens_issued_before_a_password_change() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+    let teacher = app.add_teacher("Grace", &[&class_id]).await;
+    // Tokens carry whole seconds, so the change must come a second later
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/auth/password",
+            Some(&teacher),
+            Some(json!({ "current_password": "password", "new_password": "new-password" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+
+    let (status, body) = app
+        .request(Method::GET, "/auth/me", Some(&teacher), None)
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "unauthorized");
+
+    let teacher = app.login("grace@example.com", "new-password").await;
+    let (status, _) = app
+        .request(Method::GET, "/auth/me", Some(&teacher), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn accepts_a_password_reset_for_an_unknown_email() {
+    let app = TestApp::new().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/auth/password/forgot",
+            None,
+            Some(json!({ "email": "nobody@example.com" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn resets_a_password_with_the_emailed_token() {
+    let app = TestApp::new().await;
+
+    let (status, _) = app
+        .request(
+            Method::POST,
+            "/auth/password/forgot",
+            None,
+            Some(json!({ "email": ADMIN_EMAIL })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let email = app.next_email().await;
+    assert_eq!(email.to, ADMIN_EMAIL);
+    let token = email.body.lines().nth(2).unwrap();
+
+    let reset = json!({ "token": token, "new_password": "new-secret" });
+    let (status, body) = app
+        .request(
+            Method::POST,
+            "/auth/password/reset",
+            None,
+            Some(reset.clone()),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+    app.login(ADMIN_EMAIL, "new-secret").await;
+
+    let (status, _) = app
+        .request(Method::POST, "/auth/password/reset", None, Some(reset))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn rejects_a_malformed_id_in_the_path() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app.send(Method::GET, "/students/xyz", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "invalid_input");
+}
+
+#[tokio::test]
+async fn names_the_missing_field_of_a_body() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/students/add",
+            Some(json!({ "name": "Ada", "email": "ada@example.com" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(invalid_fields(&body), ["roll_number"], "{}", body);
+}
+
+#[tokio::test]
+async fn reports_unknown_paths_and_methods() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app.send(Method::GET, "/nowhere", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "not_found");
+
+    let (status, body) = app.send(Method::PUT, "/students/add", None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body["code"], "method_not_allowed");
+}
+
+#[tokio::test]
+async fn answers_the_probes_without_a_token() {
+    let app = TestApp::new().await;
+
+    let (status, body) = app.request(Method::GET, "/health/live", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+
+    let (status, body) = app.request(Method::GET, "/health/ready", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ready");
+}
+
+#[tokio::test]
+async fn assigns_and_unassigns_the_classes_of_a_teacher() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+    let (status, teacher) = app
+        .send(
+            Method::POST,
+            "/teachers/add",
+            Some(json!({ "name": "Grace", "email": "grace@example.com" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", teacher);
+    let path = format!("/teachers/{}/classes/{}", id(&teacher), class_id);
+
+    let (status, body) = app.send(Method::POST, &path, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["classes"][0]["$oid"], class_id.as_str());
+
+    let (status, body) = app
+        .send(
+            Method::GET,
+            &format!("/teachers?class_id={}", class_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["items"].as_array().unwrap().len(), 1, "{}", body);
+
+    let (status, body) = app.send(Method::DELETE, &path, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["classes"], json!([]));
+}
+
+#[tokio::test]
+async fn blocks_deleting_a_teacher_with_an_account() {
+    let app = TestApp::new().await;
+    app.add_teacher("Grace", &[]).await;
+    let (_, page) = app.send(Method::GET, "/teachers", None).await;
+
+    let (status, body) = app
+        .send(
+            Method::DELETE,
+            &format!("/teachers/{}", id(&page["items"][0])),
+            None,
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "in_use");
+}
+
+#[tokio::test]
+async fn leaves_the_archived_classes_out_of_the_listing() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+    app.add_class("Geometry").await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/classes/{}/archive", class_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (_, active) = app.send(Method::GET, "/classes", None).await;
+    assert_eq!(active.as_array().unwrap().len(), 1, "{}", active);
+    assert_eq!(active[0]["name"], "Geometry");
+    let (_, archived) = app.send(Method::GET, "/classes?archived=true", None).await;
+    assert_eq!(archived.as_array().unwrap().len(), 1, "{}", archived);
+    assert_eq!(id(&archived[0]), class_id);
+}
+
+#[tokio::test]
+async fn rejects_enrollments_and_sessions_in_an_archived_class() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.send(
+        Method::POST,
+        &format!("/classes/{}/archive", class_id),
+        None,
+    )
+    .await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/classes/{}/enroll", class_id),
+            Some(json!({ "student_id": student_id })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "archived");
+
+    let start_time = Utc::now().timestamp();
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/classes/{}/sessions/add", class_id),
+            Some(json!({ "start_time": start_time, "end_time": start_time + 3600 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "archived");
+
+    app.send(
+        Method::POST,
+        &format!("/classes/{}/unarchive", class_id),
+        None,
+    )
+    .await;
+    app.enroll(&class_id, &student_id).await;
+}
+
+#[tokio::test]
+async fn blocks_deleting_a_class_with_enrollments() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    let empty_class = app.add_class("Geometry").await;
+    app.enroll(&class_id, &student_id).await;
+
+    let (status, body) = app
+        .send(Method::DELETE, &format!("/classes/{}", class_id), None)
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "in_use");
+
+    let (status, body) = app
+        .send(Method::DELETE, &format!("/classes/{}", empty_class), None)
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_a_mark_in_an_archived_class() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    app.send(
+        Method::POST,
+        &format!("/classes/{}/archive", class_id),
+        None,
+    )
+    .await;
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            "/attendance/mark",
+            Some(json!({ "student_id": student_id, "class_id": class_id })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "archived");
+}
+
+#[tokio::test]
+async fn clears_the_metadata_of_a_class_set_to_null() {
+    let app = TestApp::new().await;
+    let (status, class) = app
+        .send(
+            Method::POST,
+            "/classes/add",
+            Some(json!({ "name": "Algebra", "course_code": "MATH101", "credits": 3 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", class);
+
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            &format!("/classes/{}", id(&class)),
+            Some(json!({ "course_code": null })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["course_code"].is_null(), "{}", body);
+    assert_eq!(body["credits"], 3);
+}
+
+#[tokio::test]
+async fn keeps_a_session_with_records_on_its_day() {
+    let app = TestApp::new().await;
+    let student_id = app.add_student("Ada", 1).await;
+    let class_id = app.add_class("Algebra").await;
+    app.enroll(&class_id, &student_id).await;
+    let session_id = app.add_session(&class_id).await;
+    let (status, body) = app.mark_session(&student_id, &session_id).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let path = format!("/classes/{}/sessions/{}", class_id, session_id);
+
+    let start_time = Utc::now().timestamp() + 86400;
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            &path,
+            Some(json!({ "start_time": start_time, "end_time": start_time + 3600 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "in_use");
+
+    let (status, body) = app
+        .send(Method::PATCH, &path, Some(json!({ "room": "B-204" })))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_updating_a_session_of_an_archived_class() {
+    let app = TestApp::new().await;
+    let class_id = app.add_class("Algebra").await;
+    let session_id = app.add_session(&class_id).await;
+    app.send(
+        Method::POST,
+        &format!("/classes/{}/archive", class_id),
+        None,
+    )
+    .await;
+
+    let (status, body) = app
+        .send(
+            Method::PATCH,
+            &format!("/classes/{}/sessions/{}", class_id, session_id),
+            Some(json!({ "room": "B-204" })),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "archived");
+}
@@ -0,0 +1,231 @@
+//! This module defines the multi-document transactions. A write made of several
+//! steps, such as checking that a student exists before marking their attendance,
+//! runs through `Transactions::run`. With MongoDB, the steps then run in a single
+//! transaction, retried when the server reports a transient error such as a write
+//! conflict. The MongoDB repositories find the transaction of the current task
+//! through `Transaction::current`, so the routes and the repository traits do not
+//! have to pass a session around.
+//!
+//! Transactions need a replica set or a sharded cluster. Against a standalone
+//! server, and with the in-memory storage, the steps run without a transaction,
+//! and the multi-step writes run one at a time so that the steps of two writes
+//! cannot interleave.
+
+use crate::error::ErrorType;
+use mongodb::{
+    Client, ClientSession,
+    action::Find,
+    bson::doc,
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+};
+use serde::de::DeserializeOwned;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_stream::StreamExt;
+use tracing::{error, info, warn};
+
+/// How many times a transaction is tried before its transient error is returned.
+const MAX_TRANSACTION_ATTEMPTS: u32 = 5;
+
+tokio::task_local! {
+    /// The transaction the current task runs its MongoDB operations in.
+    static CURRENT_TRANSACTION: Arc<Transaction>;
+}
+
+/// A MongoDB transaction in progress.
+pub struct Transaction {
+    session: Mutex<ClientSession>,
+    transient_error: AtomicBool,
+}
+
+impl Transaction {
+    /// Returns the transaction the current task runs in, or `None` outside of one.
+    pub fn current() -> Option<Arc<Transaction>> {
+        CURRENT_TRANSACTION.try_with(Arc::clone).ok()
+    }
+
+    /// Locks the session of the transaction for the duration of one operation.
+    pub async fn session(&self) -> MutexGuard<'_, ClientSession> {
+        self.session.lock().await
+    }
+
+    /// Remembers whether an operation failed with an error that makes the whole
+    /// transaction worth retrying. The repositories map the MongoDB errors to
+    /// `ErrorType`, so the label would be lost otherwise.
+    pub fn record_error(&self, err: &mongodb::error::Error) {
+        if err.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+            self.transient_error.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Runs a MongoDB action in the transaction of the current task, if there is one.
+/// Errors are recorded on the transaction, so that transient ones lead to a retry.
+macro_rules! in_transaction {
+    ($action:expr) => {
+        match $crate::transaction::Transaction::current() {
+            Some(transaction) => {
+                let result = $action.session(&mut *transaction.session().await).await;
+                if let Err(err) = &result {
+                    transaction.record_error(err);
+                }
+                result
+            }
+            None => $action.await,
+        }
+    };
+}
+pub(crate) use in_transaction;
+
+/// Runs a find in the transaction of the current task, if there is one, and
+/// collects every document it returns.
+pub async fn collect<T>(find: Find<'_, T>) -> Result<Vec<T>, mongodb::error::Error>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    let mut documents = Vec::new();
+
+    let Some(transaction) = Transaction::current() else {
+        let mut cursor = find.await?;
+        while let Some(document) = cursor.try_next().await? {
+            documents.push(document);
+        }
+        return Ok(documents);
+    };
+
+    let mut session = transaction.session().await;
+    let result = async {
+        let mut cursor = find.session(&mut *session).await?;
+        while let Some(document) = cursor.next(&mut session).await {
+            documents.push(document?);
+        }
+        Ok(documents)
+    }
+    .await;
+
+    if let Err(err) = &result {
+        transaction.record_error(err);
+    }
+    result
+}
+
+/// Runs multi-step writes in transactions when the deployment supports them.
+#[derive(Clone, Default)]
+pub struct Transactions {
+    client: Option<Client>,
+    /// Held by the write running without a transaction
+    serial: Arc<Mutex<()>>,
+}
+
+impl Transactions {
+    /// Runs the writes without transactions, one at a time, for the in-memory
+    /// storage.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Runs the writes in transactions of the given client if its deployment is a
+    /// replica set or a sharded cluster, and without transactions otherwise.
+    pub async fn mongo(client: &Client) -> Result<Self, ErrorType> {
+        let hello = client
+            .database("admin")
+            .run_command(doc! { "hello": 1 })
+            .await
+            .map_err(|err| {
+                error!(error = %err, "Error checking the MongoDB deployment");
+                ErrorType::ServerError("Server Error".to_string())
+            })?;
+
+        let is_replica_set = hello.get_str("setName").is_ok();
+        let is_sharded = hello.get_str("msg") == Ok("isdbgrid");
+        if !is_replica_set && !is_sharded {
+            warn!("MongoDB is a standalone server, so multi-step writes run without transactions");
+            return Ok(Self::disabled());
+        }
+
+        info!("Multi-step writes run in MongoDB transactions");
+        Ok(Self {
+            client: Some(client.clone()),
+            ..Self::default()
+        })
+    }
+
+    /// Runs `work` in a transaction, committed if it succeeds and aborted if it
+    /// fails. The whole transaction is tried again when it fails with a transient
+    /// error, so `work` may run several times and must not have effects outside of
+    /// the database. Without transactions, `work` runs once, after the multi-step
+    /// write running at the same time has ended.
+    pub async fn run<T, F, Fut>(&self, work: F) -> Result<T, ErrorType>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ErrorType>>,
+    {
+        let Some(client) = &self.client else {
+            let _serial = self.serial.lock().await;
+            return work().await;
+        };
+
+        let mut attempt = 1;
+        'attempts: loop {
+            let mut session = client.start_session().await.map_err(server_error)?;
+            session.start_transaction().await.map_err(server_error)?;
+
+            let transaction = Arc::new(Transaction {
+                session: Mutex::new(session),
+                transient_error: AtomicBool::new(false),
+            });
+            let result = CURRENT_TRANSACTION
+                .scope(Arc::clone(&transaction), work())
+                .await;
+            let mut session = transaction.session().await;
+
+            let value = match result {
+                Ok(value) => value,
+                Err(err) => {
+                    // The server may have aborted the transaction already.
+                    let _ = session.abort_transaction().await;
+                    if transaction.transient_error.load(Ordering::Relaxed)
+                        && attempt < MAX_TRANSACTION_ATTEMPTS
+                    {
+                        warn!(attempt, "Retrying a transaction after a transient error");
+                        attempt += 1;
+                        continue 'attempts;
+                    }
+                    return Err(err);
+                }
+            };
+
+            loop {
+                match session.commit_transaction().await {
+                    Ok(()) => return Ok(value),
+                    Err(err)
+                        if err.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT)
+                            && attempt < MAX_TRANSACTION_ATTEMPTS =>
+                    {
+                        warn!(attempt, "Retrying the commit of a transaction");
+                        attempt += 1;
+                    }
+                    Err(err)
+                        if err.contains_label(TRANSIENT_TRANSACTION_ERROR)
+                            && attempt < MAX_TRANSACTION_ATTEMPTS =>
+                    {
+                        warn!(attempt, "Retrying a transaction after a transient error");
+                        attempt += 1;
+                        continue 'attempts;
+                    }
+                    Err(err) => return Err(server_error(err)),
+                }
+            }
+        }
+    }
+}
+
+/// Logs a failure to start or commit a transaction and maps it to
+/// `ErrorType::ServerError`.
+fn server_error(err: mongodb::error::Error) -> ErrorType {
+    error!(error = %err, "Error running a transaction");
+    ErrorType::ServerError("Server Error".to_string())
+}
@@ -0,0 +1,413 @@
+//! This module defines the validation of the models received by the API. Every
+//! field is checked before anything is stored, and all the failing fields are
+//! reported at once through `ErrorType::Validation`.
+
+use crate::error::{ErrorType, FieldError};
+use crate::models::attendance_model::MarkAttendanceRequest;
+use crate::models::class_model::{Class, ScheduleSlot};
+use crate::models::enrollment_model::EnrollRequest;
+use crate::models::session_model::Session;
+use crate::models::student_model::Student;
+use crate::models::teacher_model::{Teacher, TeacherUpdate};
+use crate::models::user_model::{NewUser, PasswordChangeRequest, PasswordResetConfirm, Role};
+use crate::state::AppState;
+use async_trait::async_trait;
+use chrono::Utc;
+
+/// Collects the failing fields of a model.
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Creates a validator with no failing field.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a failing field unless the condition holds.
+    pub fn check(&mut self, field: &str, condition: bool, message: &str) -> &mut Self {
+        if !condition {
+            self.errors.push(FieldError {
+                field: field.to_string(),
+                message: message.to_string(),
+            });
+        }
+        self
+    }
+
+    /// Checks that a text is not empty once trimmed.
+    pub fn non_empty(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(field, !value.trim().is_empty(), "must not be empty")
+    }
+
+    /// Checks that a text is a syntactically valid email address.
+    pub fn email(&mut self, field: &str, value: &str) -> &mut Self {
+        self.check(
+            field,
+            is_valid_email(value),
+            "must be a valid email address",
+        )
+    }
+
+    /// Checks that a number is strictly positive.
+    pub fn positive(&mut self, field: &str, value: i32) -> &mut Self {
+        self.check(field, value > 0, "must be a positive number")
+    }
+
+    /// Returns `ErrorType::Validation` listing every failing field, if any.
+    pub fn finish(self) -> Result<(), ErrorType> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorType::Validation(self.errors))
+        }
+    }
+}
+
+/// Tells whether a text looks like an email address: a non-empty local part, an
+/// `@`, and a domain of dot-separated non-empty labels, without any whitespace.
+pub fn is_valid_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|character| character.is_alphanumeric() || character == '-')
+        })
+}
+
+/// A model received by the API that can be checked before it is stored.
+#[async_trait]
+pub trait Validate {
+    /// Trims the text fields of the model, so that what is validated is what is
+    /// stored.
+    fn normalize(&mut self) {}
+
+    /// Checks every field of the model, including that the IDs it refers to exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ErrorType::Validation` listing every failing field, or
+    /// `ErrorType::ServerError` if a referenced ID cannot be looked up.
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType>;
+}
+
+#[async_trait]
+impl Validate for Student {
+    fn normalize(&mut self) {
+        self.name = self.name.trim().to_string();
+        self.email = self.email.trim().to_string();
+    }
+
+    async fn validate(&self, _state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator
+            .non_empty("name", &self.name)
+            .email("email", &self.email)
+            .positive("roll_number", self.roll_number);
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for Teacher {
+    fn normalize(&mut self) {
+        self.name = self.name.trim().to_string();
+        self.email = self.email.trim().to_string();
+    }
+
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator
+            .non_empty("name", &self.name)
+            .email("email", &self.email);
+
+        let (mut all_exist, mut any_archived) = (true, false);
+        for class_id in &self.classes {
+            match state.classes.find_by_id(*class_id).await? {
+                Some(class) => any_archived |= class.archived,
+                None => all_exist = false,
+            }
+        }
+        let repeated = self
+            .classes
+            .iter()
+            .enumerate()
+            .any(|(index, class_id)| self.classes[..index].contains(class_id));
+        validator
+            .check("classes", all_exist, "must only refer to existing classes")
+            .check("classes", !repeated, "must not contain a class twice")
+            .check(
+                "classes",
+                !any_archived,
+                "must not contain an archived class",
+            );
+
+        validator.finish()
+    }
+}
+
+/// The update of a teacher leaves their classes unchanged, so only the fields it
+/// changes are checked; a class archived since it was assigned does not block it.
+#[async_trait]
+impl Validate for TeacherUpdate {
+    fn normalize(&mut self) {
+        let trim =
+            |text: &mut Option<String>| *text = text.take().map(|text| text.trim().to_string());
+
+        trim(&mut self.name);
+        trim(&mut self.email);
+    }
+
+    async fn validate(&self, _state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        if let Some(name) = &self.name {
+            validator.non_empty("name", name);
+        }
+        if let Some(email) = &self.email {
+            validator.email("email", email);
+        }
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for Class {
+    fn normalize(&mut self) {
+        let trim =
+            |text: &mut Option<String>| *text = text.take().map(|text| text.trim().to_string());
+
+        self.name = self.name.trim().to_string();
+        trim(&mut self.course_code);
+        trim(&mut self.department);
+        trim(&mut self.term);
+        for slot in &mut self.schedule {
+            trim(&mut slot.room);
+        }
+    }
+
+    async fn validate(&self, _state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.non_empty("name", &self.name);
+        for (field, value) in [
+            ("course_code", &self.course_code),
+            ("department", &self.department),
+            ("term", &self.term),
+        ] {
+            if let Some(value) = value {
+                validator.non_empty(field, value);
+            }
+        }
+        if let Some(credits) = self.credits {
+            validator.check("credits", credits >= 0, "must not be negative");
+        }
+        if let Some(capacity) = self.capacity {
+            validator.positive("capacity", capacity);
+        }
+        validator
+            .check(
+                "schedule",
+                self.schedule
+                    .iter()
+                    .all(|slot| slot.end_time > slot.start_time),
+                "must only have slots ending after they start",
+            )
+            .check(
+                "schedule",
+                self.schedule
+                    .iter()
+                    .all(|slot| slot.room.as_deref().is_none_or(|room| !room.is_empty())),
+                "must not have an empty room",
+            )
+            .check(
+                "schedule",
+                !has_overlapping_slots(&self.schedule),
+                "must not have overlapping slots",
+            );
+
+        validator.finish()
+    }
+}
+
+/// Tells whether two slots of a schedule meet on the same day at overlapping times.
+fn has_overlapping_slots(schedule: &[ScheduleSlot]) -> bool {
+    schedule.iter().enumerate().any(|(index, slot)| {
+        schedule[..index].iter().any(|other| {
+            other.weekday == slot.weekday
+                && other.start_time < slot.end_time
+                && slot.start_time < other.end_time
+        })
+    })
+}
+
+#[async_trait]
+impl Validate for Session {
+    fn normalize(&mut self) {
+        self.room = self.room.take().map(|room| room.trim().to_string());
+    }
+
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.check(
+            "end_time",
+            self.end_time > self.start_time,
+            "must be after the start time",
+        );
+        if let Some(room) = &self.room {
+            validator.non_empty("room", room);
+        }
+        if let Some(teacher_id) = self.teacher_id {
+            validator.check(
+                "teacher_id",
+                state.teachers.find_by_id(teacher_id).await?.is_some(),
+                "does not refer to an existing teacher",
+            );
+        }
+
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for EnrollRequest {
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.check(
+            "student_id",
+            state.students.find_by_id(self.student_id).await?.is_some(),
+            "does not refer to an existing student",
+        );
+        // An enrollment without a start date starts today
+        let start_date = self.start_date.unwrap_or_else(|| Utc::now().date_naive());
+        validator.check(
+            "end_date",
+            self.end_date.is_none_or(|end_date| start_date <= end_date),
+            "must not be before the start date",
+        );
+
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for MarkAttendanceRequest {
+    fn normalize(&mut self) {
+        self.reason = self.reason.take().map(|reason| reason.trim().to_string());
+    }
+
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.check(
+            "student_id",
+            state.students.find_by_id(self.student_id).await?.is_some(),
+            "does not refer to an existing student",
+        );
+        validator.check(
+            "class_id",
+            self.class_id.is_some() || self.session_id.is_some(),
+            "is required when there is no session_id",
+        );
+        if let Some(class_id) = self.class_id {
+            validator.check(
+                "class_id",
+                state.classes.find_by_id(class_id).await?.is_some(),
+                "does not refer to an existing class",
+            );
+        }
+        if let Some(session_id) = self.session_id {
+            match state.sessions.find_by_id(session_id).await? {
+                Some(session) => validator.check(
+                    "session_id",
+                    self.class_id
+                        .is_none_or(|class_id| class_id == session.class_id),
+                    "does not belong to the class",
+                ),
+                None => {
+                    validator.check("session_id", false, "does not refer to an existing session")
+                }
+            };
+        }
+
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for NewUser {
+    fn normalize(&mut self) {
+        self.email = self.email.trim().to_string();
+    }
+
+    async fn validate(&self, state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.email("email", &self.email).check(
+            "password",
+            !self.password.is_empty(),
+            "must not be empty",
+        );
+
+        match self.role {
+            Role::Admin => {}
+            Role::Student => match self.student_id {
+                Some(student_id) => {
+                    validator.check(
+                        "student_id",
+                        state.students.find_by_id(student_id).await?.is_some(),
+                        "does not refer to an existing student",
+                    );
+                }
+                None => {
+                    validator.check("student_id", false, "is required for the student role");
+                }
+            },
+            Role::Teacher => match self.teacher_id {
+                Some(teacher_id) => {
+                    validator.check(
+                        "teacher_id",
+                        state.teachers.find_by_id(teacher_id).await?.is_some(),
+                        "does not refer to an existing teacher",
+                    );
+                }
+                None => {
+                    validator.check("teacher_id", false, "is required for the teacher role");
+                }
+            },
+        }
+
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for PasswordChangeRequest {
+    async fn validate(&self, _state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.check(
+            "new_password",
+            !self.new_password.is_empty(),
+            "must not be empty",
+        );
+        validator.finish()
+    }
+}
+
+#[async_trait]
+impl Validate for PasswordResetConfirm {
+    async fn validate(&self, _state: &AppState) -> Result<(), ErrorType> {
+        let mut validator = Validator::new();
+        validator.check(
+            "new_password",
+            !self.new_password.is_empty(),
+            "must not be empty",
+        );
+        validator.finish()
+    }
+}